@@ -0,0 +1,169 @@
+use crate::errors::{AppError, AppResult};
+use crate::models::{format_ms, AggregationMethod, AggregationPolicy, Rounding, SourceReading, UsdAmount};
+
+/// Latest value fetched from one source of a mint.
+#[derive(Debug, Clone)]
+pub struct Reading {
+	pub source: String,
+	pub kind: &'static str,
+	pub amount: UsdAmount,
+	pub weight: u32,
+	/// Confidence interval, for sources that report one
+	pub conf: Option<UsdAmount>,
+	pub read_at_ms: i64,
+}
+
+/// Result of [`aggregate`]: the combined price, its confidence when every used reading has one (the
+/// widest of them), and the status of each reading.
+pub struct Aggregate {
+	pub amount: UsdAmount,
+	pub conf: Option<UsdAmount>,
+	pub report: Vec<SourceReading>,
+}
+
+/// Combines one mint's readings under `policy`. Readings older than `max_reading_age_secs` are stale,
+/// fresh readings further than `max_deviation_bps` from their median are outliers, and the rest must
+/// number at least `min_sources`. The result is at the largest scale among the readings; every reading
+/// is returned with the status it was given.
+pub fn aggregate(readings: &[Reading], policy: &AggregationPolicy, now_ms: i64) -> AppResult<Aggregate> {
+	let max_age_ms = i64::try_from(policy.max_reading_age_secs.saturating_mul(1000)).unwrap_or(i64::MAX);
+	let is_fresh = |r: &Reading| now_ms.saturating_sub(r.read_at_ms) <= max_age_ms;
+	let center = median(readings.iter().filter(|r| is_fresh(r)).map(|r| r.amount).collect());
+
+	let mut used = Vec::new();
+	let mut report = Vec::with_capacity(readings.len());
+	for r in readings {
+		let fresh = is_fresh(r);
+		let deviation = center.filter(|_| fresh).and_then(|c| c.change_bps(&r.amount));
+		let status = if !fresh {
+			"stale"
+		} else if matches!((policy.max_deviation_bps, deviation), (Some(limit), Some(d)) if d > limit as u128) {
+			"outlier"
+		} else {
+			used.push(r);
+			"used"
+		};
+		report.push(SourceReading {
+			source: r.source.clone(),
+			kind: r.kind.to_string(),
+			usd_mantissa: r.amount.mantissa().to_string(),
+			usd_scale: r.amount.scale(),
+			weight: r.weight,
+			read_at: format_ms(r.read_at_ms),
+			status: status.into(),
+			deviation_bps: deviation.map(|d| u64::try_from(d).unwrap_or(u64::MAX)),
+		});
+	}
+
+	let quorum = policy.min_sources.max(1) as usize;
+	if used.len() < quorum {
+		return Err(AppError::NoPrice(format!("quorum not met: {} usable readings, {} required", used.len(), quorum)));
+	}
+	let scale = used.iter().map(|r| r.amount.scale()).max().unwrap_or_default();
+	let amount = match policy.method {
+		AggregationMethod::Median => median(used.iter().map(|r| r.amount).collect()),
+		AggregationMethod::WeightedMean => weighted_mean(&used, scale),
+	};
+	let amount = amount.ok_or_else(|| AppError::BadRequest("readings cannot be aggregated (zero total weight or overflow)".into()))?;
+	let conf = used.iter().map(|r| r.conf).collect::<Option<Vec<_>>>().and_then(|c| c.into_iter().max());
+	Ok(Aggregate { amount: amount.rescale(scale, Rounding::HalfEven)?, conf, report })
+}
+
+/// Middle value, or the mean of the two middle values for an even count.
+fn median(mut values: Vec<UsdAmount>) -> Option<UsdAmount> {
+	values.sort();
+	let n = values.len();
+	if n == 0 { return None; }
+	if n % 2 == 1 { return Some(values[n / 2]); }
+	let (a, b) = (values[n / 2 - 1], values[n / 2]);
+	let two = UsdAmount::new(2, 0).ok()?;
+	a.checked_add(&b)?.checked_div(&two, a.scale().max(b.scale()), Rounding::HalfEven)
+}
+
+fn weighted_mean(readings: &[&Reading], scale: u32) -> Option<UsdAmount> {
+	let mut sum = UsdAmount::zero();
+	let mut total: u128 = 0;
+	for r in readings {
+		let weight = UsdAmount::new(r.weight as u128, 0).ok()?;
+		sum = sum.checked_add(&r.amount.checked_mul(&weight)?)?;
+		total += r.weight as u128;
+	}
+	sum.checked_div(&UsdAmount::new(total, 0).ok()?, scale, Rounding::HalfEven)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NOW: i64 = 1_700_000_000_000;
+
+	fn reading(source: &str, price: &str, weight: u32, age_secs: i64) -> Reading {
+		Reading {
+			source: source.into(),
+			kind: "static",
+			amount: UsdAmount::from_decimal_str(price).unwrap(),
+			weight,
+			conf: None,
+			read_at_ms: NOW - age_secs * 1000,
+		}
+	}
+
+	fn policy(method: AggregationMethod, max_deviation_bps: Option<u32>, min_sources: u32) -> AggregationPolicy {
+		AggregationPolicy { method, max_deviation_bps, min_sources, max_reading_age_secs: 60 }
+	}
+
+	fn statuses(agg: &Aggregate) -> Vec<&str> {
+		agg.report.iter().map(|r| r.status.as_str()).collect()
+	}
+
+	#[test]
+	fn median_of_odd_and_even_counts() {
+		let median = policy(AggregationMethod::Median, None, 1);
+		let odd = [reading("a", "1.00", 1, 0), reading("b", "3.00", 1, 0), reading("c", "2.00", 1, 0)];
+		assert_eq!(aggregate(&odd, &median, NOW).unwrap().amount.to_string(), "2.00");
+		let even = [reading("a", "1.00", 1, 0), reading("b", "2.5", 1, 0), reading("c", "2.00", 1, 0), reading("d", "4.00", 1, 0)];
+		assert_eq!(aggregate(&even, &median, NOW).unwrap().amount.to_string(), "2.25");
+		// the mean of the middle pair is rounded half-even at the largest reading scale
+		let tie = [reading("a", "1.01", 1, 0), reading("b", "1.02", 1, 0)];
+		assert_eq!(aggregate(&tie, &median, NOW).unwrap().amount.to_string(), "1.02");
+	}
+
+	#[test]
+	fn outliers_and_stale_readings_are_excluded() {
+		let readings = [reading("a", "1.00", 1, 0), reading("b", "1.01", 1, 0), reading("c", "1.50", 1, 0), reading("d", "9.00", 1, 120)];
+		let agg = aggregate(&readings, &policy(AggregationMethod::Median, Some(200), 1), NOW).unwrap();
+		assert_eq!(statuses(&agg), ["used", "used", "outlier", "stale"]);
+		// band is measured from the median of all fresh readings (1.01), the price from the used ones
+		assert_eq!(agg.amount.to_string(), "1.00");
+		assert_eq!(agg.report[2].deviation_bps, Some(4852));
+		assert_eq!(agg.report[3].deviation_bps, None);
+	}
+
+	#[test]
+	fn quorum_counts_usable_readings() {
+		let readings = [reading("a", "1.00", 1, 0), reading("b", "5.00", 1, 0), reading("c", "1.00", 1, 120)];
+		let err = aggregate(&readings, &policy(AggregationMethod::Median, Some(100), 2), NOW).err().unwrap();
+		assert!(matches!(err, AppError::NoPrice(_)), "{}", err);
+		assert!(aggregate(&readings, &policy(AggregationMethod::Median, None, 2), NOW).is_ok());
+		assert!(aggregate(&[], &policy(AggregationMethod::Median, None, 0), NOW).is_err());
+	}
+
+	#[test]
+	fn weighted_mean_uses_weights() {
+		let mean = policy(AggregationMethod::WeightedMean, None, 1);
+		let readings = [reading("a", "1.00", 3, 0), reading("b", "2.00", 1, 0)];
+		assert_eq!(aggregate(&readings, &mean, NOW).unwrap().amount.to_string(), "1.25");
+		let unweighted = [reading("a", "1.00", 0, 0), reading("b", "2.00", 0, 0)];
+		assert!(matches!(aggregate(&unweighted, &mean, NOW), Err(AppError::BadRequest(_))));
+	}
+
+	#[test]
+	fn conf_is_widest_when_every_reading_has_one() {
+		let median = policy(AggregationMethod::Median, None, 1);
+		let mut readings = [reading("a", "1.00", 1, 0), reading("b", "1.02", 1, 0)];
+		readings[0].conf = Some(UsdAmount::from_decimal_str("0.01").unwrap());
+		assert!(aggregate(&readings, &median, NOW).unwrap().conf.is_none());
+		readings[1].conf = Some(UsdAmount::from_decimal_str("0.03").unwrap());
+		assert_eq!(aggregate(&readings, &median, NOW).unwrap().conf.unwrap().to_string(), "0.03");
+	}
+}
@@ -9,7 +9,7 @@ struct Args {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct Price { mint: String, symbol: Option<String>, usd_mantissa: String, usd_scale: u32, updated_at: String, updated_by: String, decimals: Option<u8> }
+struct Price { mint: String, symbol: Option<String>, usd_mantissa: String, usd_scale: u32, #[serde(default)] conf_mantissa: Option<String>, updated_at: String, updated_by: String, decimals: Option<u8>, #[serde(default)] version: u64 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -22,7 +22,7 @@ async fn main() -> anyhow::Result<()> {
 	loop {
 		let prices = list_prices(&client, base).await.unwrap_or_default();
 		println!("\nPrices ({}):", prices.len());
-		for p in &prices { println!("- {} {} mantissa={} scale={} dec={:?}", p.mint, p.symbol.clone().unwrap_or_default(), p.usd_mantissa, p.usd_scale, p.decimals); }
+		for p in &prices { println!("- {} {} mantissa={} scale={} conf={} dec={:?}", p.mint, p.symbol.clone().unwrap_or_default(), p.usd_mantissa, p.usd_scale, p.conf_mantissa.as_deref().unwrap_or("-"), p.decimals); }
 		if !Confirm::new().with_prompt("Edit a price?").default(false).interact()? { break; }
 		let mint: String = Input::new().with_prompt("mint").interact_text()?;
 		let symbol: String = Input::new().with_prompt("symbol (empty=skip)").allow_empty(true).interact_text()?;
@@ -34,9 +34,16 @@ async fn main() -> anyhow::Result<()> {
 		if !mantissa.is_empty() { body.insert("usd_mantissa".into(), serde_json::Value::String(mantissa)); }
 		if let Ok(v) = scale_s.parse::<u32>() { body.insert("usd_scale".into(), serde_json::Value::from(v)); }
 		if let Ok(v) = decimals_s.parse::<u8>() { body.insert("decimals".into(), serde_json::Value::from(v)); }
-		let _ = client.patch(format!("{}/api/v1/prices/{}", base, mint))
-			.header("Authorization", format!("Bearer {}", token))
-			.json(&serde_json::Value::Object(body)).send().await?;
+		let mut req = client.patch(format!("{}/api/v1/prices/{}", base, mint))
+			.header("Authorization", format!("Bearer {}", token));
+		// only apply the edit if nobody changed the price since it was listed
+		if let Some(p) = prices.iter().find(|p| p.mint == mint) { req = req.header("If-Match", format!("\"{}\"", p.version)); }
+		let r = req.json(&serde_json::Value::Object(body)).send().await?;
+		if r.status() == reqwest::StatusCode::CONFLICT {
+			println!("Conflict: {} was changed by someone else; review the refreshed list and retry.", mint);
+		} else if !r.status().is_success() {
+			println!("Update failed: {}", r.status());
+		}
 	}
 	Ok(())
 }
@@ -0,0 +1,140 @@
+use serde::Serialize;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{format_ms, Price, UsdAmount};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+	OneMinute,
+	FiveMinutes,
+	OneHour,
+	OneDay,
+}
+
+impl Interval {
+	pub fn parse(s: &str) -> AppResult<Self> {
+		match s {
+			"1m" => Ok(Interval::OneMinute),
+			"5m" => Ok(Interval::FiveMinutes),
+			"1h" => Ok(Interval::OneHour),
+			"1d" => Ok(Interval::OneDay),
+			_ => Err(AppError::BadRequest("interval must be one of 1m, 5m, 1h, 1d".into())),
+		}
+	}
+
+	pub fn millis(&self) -> i64 {
+		match self {
+			Interval::OneMinute => 60_000,
+			Interval::FiveMinutes => 300_000,
+			Interval::OneHour => 3_600_000,
+			Interval::OneDay => 86_400_000,
+		}
+	}
+
+	/// Start of the bucket containing `ts_ms`, aligned to the unix epoch.
+	pub fn bucket_start(&self, ts_ms: i64) -> i64 {
+		ts_ms.div_euclid(self.millis()) * self.millis()
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candle {
+	pub start: String,
+	pub open: UsdAmount,
+	pub high: UsdAmount,
+	pub low: UsdAmount,
+	pub close: UsdAmount,
+	pub updates: u32,
+}
+
+struct Bucket {
+	start_ms: i64,
+	open: UsdAmount,
+	high: UsdAmount,
+	low: UsdAmount,
+	close: UsdAmount,
+	updates: u32,
+}
+
+/// Aggregates time-ordered `(ts_ms, price)` rows into OHLC candles; buckets without updates are omitted.
+/// Rows whose mantissa is not a valid `UsdAmount` are skipped.
+pub fn build_candles(points: &[(i64, Price)], interval: Interval) -> Vec<Candle> {
+	let mut buckets: Vec<Bucket> = Vec::new();
+	for (ts_ms, price) in points {
+		let Ok(value) = price.usd_amount() else { continue };
+		let start_ms = interval.bucket_start(*ts_ms);
+		match buckets.last_mut() {
+			Some(b) if b.start_ms == start_ms => {
+				if value > b.high { b.high = value; }
+				if value < b.low { b.low = value; }
+				b.close = value;
+				b.updates += 1;
+			}
+			_ => buckets.push(Bucket { start_ms, open: value, high: value, low: value, close: value, updates: 1 }),
+		}
+	}
+	buckets
+		.into_iter()
+		.map(|b| Candle {
+			start: format_ms(b.start_ms),
+			open: b.open,
+			high: b.high,
+			low: b.low,
+			close: b.close,
+			updates: b.updates,
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const T0: i64 = 1_700_000_080_000; // 40s into a minute
+
+	fn point(ts_ms: i64, mantissa: &str) -> (i64, Price) {
+		let mut price = Price::next_from(None, "M", UsdAmount::zero(), "test");
+		price.usd_mantissa = mantissa.into();
+		price.usd_scale = 2;
+		(ts_ms, price)
+	}
+
+	fn ohlc(c: &Candle) -> [String; 4] {
+		[c.open, c.high, c.low, c.close].map(|v| v.to_string())
+	}
+
+	#[test]
+	fn no_points_no_candles() {
+		assert!(build_candles(&[], Interval::OneMinute).is_empty());
+	}
+
+	#[test]
+	fn single_point_is_flat() {
+		let candles = build_candles(&[point(T0, "150")], Interval::OneMinute);
+		assert_eq!(candles.len(), 1);
+		assert_eq!(ohlc(&candles[0]), ["1.50", "1.50", "1.50", "1.50"]);
+		assert_eq!(candles[0].updates, 1);
+		assert_eq!(candles[0].start, format_ms(T0 - 40_000));
+	}
+
+	#[test]
+	fn open_and_close_follow_time_order() {
+		let points = [point(T0, "150"), point(T0 + 5_000, "190"), point(T0 + 10_000, "120"), point(T0 + 15_000, "160")];
+		let candles = build_candles(&points, Interval::OneMinute);
+		assert_eq!(candles.len(), 1);
+		assert_eq!(ohlc(&candles[0]), ["1.50", "1.90", "1.20", "1.60"]);
+		assert_eq!(candles[0].updates, 4);
+	}
+
+	#[test]
+	fn empty_buckets_are_omitted() {
+		// the third point lands four minutes on; invalid mantissas are skipped
+		let points = [point(T0, "100"), point(T0 + 5_000, "110"), point(T0 + 200_000, "90"), point(T0 + 201_000, "bad")];
+		let candles = build_candles(&points, Interval::OneMinute);
+		assert_eq!(candles.len(), 2);
+		assert_eq!(ohlc(&candles[0]), ["1.00", "1.10", "1.00", "1.10"]);
+		assert_eq!(ohlc(&candles[1]), ["0.90", "0.90", "0.90", "0.90"]);
+		assert_eq!(candles[1].start, format_ms(T0 - 40_000 + 4 * 60_000));
+		assert_eq!(candles[1].updates, 1);
+	}
+}
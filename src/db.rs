@@ -2,10 +2,12 @@ use std::path::PathBuf;
 
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use r2d2_sqlite::rusqlite::{params, OptionalExtension};
+use r2d2_sqlite::rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
 
 use crate::errors::{AppError, AppResult};
-use crate::models::{AuditEntry, Config, Price, SymbolMap};
+use crate::models::{parse_rfc3339_ms, AggregationPolicy, AuditEntry, Config, FrozenMint, Price, Rounding, ScheduledPrice, SourceReading, SymbolMap};
+use crate::sources::{PegSource, SourceSpec};
 
 #[derive(Clone)]
 pub struct DbState {
@@ -15,7 +17,11 @@ pub struct DbState {
 impl DbState {
 	pub fn initialize() -> AppResult<Self> {
 		let db_path = std::env::var("ORACLE_DB_PATH").unwrap_or_else(|_| "./oracle.sqlite".into());
-		let path = PathBuf::from(db_path);
+		Self::open(PathBuf::from(db_path))
+	}
+
+	/// Opens (creating if needed) and migrates the database at `path`.
+	pub fn open(path: PathBuf) -> AppResult<Self> {
 		let manager = SqliteConnectionManager::file(path).with_init(|c| {
 			c.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")
 		});
@@ -74,15 +80,95 @@ impl DbState {
 				last_accessed INTEGER NOT NULL
 			);
 			CREATE INDEX IF NOT EXISTS idx_http_cache_expires ON http_cache (expires_at);
-			CREATE INDEX IF NOT EXISTS idx_http_cache_popularity ON http_cache (popularity DESC);",
+			CREATE INDEX IF NOT EXISTS idx_http_cache_popularity ON http_cache (popularity DESC);
+			CREATE TABLE IF NOT EXISTS price_history (
+				id INTEGER PRIMARY KEY AUTOINCREMENT,
+				mint TEXT NOT NULL,
+				symbol TEXT,
+				usd_mantissa TEXT NOT NULL,
+				usd_scale INTEGER NOT NULL,
+				updated_at TEXT NOT NULL,
+				updated_by TEXT NOT NULL,
+				decimals INTEGER,
+				ts_ms INTEGER NOT NULL -- updated_at as unix millis, for range queries
+			);
+			CREATE INDEX IF NOT EXISTS idx_price_history_mint_ts ON price_history (mint, ts_ms);
+			CREATE TABLE IF NOT EXISTS scheduled_prices (
+				id TEXT PRIMARY KEY,
+				mint TEXT NOT NULL,
+				symbol TEXT,
+				usd_mantissa TEXT NOT NULL,
+				usd_scale INTEGER NOT NULL,
+				decimals INTEGER,
+				run_at TEXT NOT NULL,
+				run_at_ms INTEGER NOT NULL,
+				status TEXT NOT NULL DEFAULT 'pending', -- pending | applied | failed
+				created_at TEXT NOT NULL,
+				created_by TEXT NOT NULL,
+				applied_at TEXT,
+				error TEXT
+			);
+			CREATE INDEX IF NOT EXISTS idx_scheduled_prices_due ON scheduled_prices (status, run_at_ms);
+			CREATE TABLE IF NOT EXISTS frozen_mints (
+				mint TEXT PRIMARY KEY,
+				reason TEXT NOT NULL,
+				frozen_at TEXT NOT NULL,
+				frozen_by TEXT NOT NULL,
+				auto INTEGER NOT NULL DEFAULT 0
+			);
+			CREATE TABLE IF NOT EXISTS source_readings (
+				id INTEGER PRIMARY KEY AUTOINCREMENT,
+				mint TEXT NOT NULL,
+				price_version INTEGER NOT NULL, -- version of the aggregated price these readings produced
+				source TEXT NOT NULL,
+				kind TEXT NOT NULL,
+				usd_mantissa TEXT NOT NULL,
+				usd_scale INTEGER NOT NULL,
+				weight INTEGER NOT NULL,
+				read_at TEXT NOT NULL,
+				status TEXT NOT NULL, -- used | outlier | stale
+				deviation_bps INTEGER
+			);
+			CREATE INDEX IF NOT EXISTS idx_source_readings_mint ON source_readings (mint, price_version);
+			CREATE TABLE IF NOT EXISTS peg_sources (
+				id TEXT PRIMARY KEY,
+				mint TEXT NOT NULL,
+				spec TEXT NOT NULL, -- JSON SourceSpec
+				created_at TEXT NOT NULL,
+				created_by TEXT NOT NULL,
+				updated_at TEXT NOT NULL,
+				updated_by TEXT NOT NULL
+			);
+			CREATE INDEX IF NOT EXISTS idx_peg_sources_mint ON peg_sources (mint);
+			-- backfill: every current price gets at least one history row
+			INSERT INTO price_history (mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals, ts_ms)
+			SELECT mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals,
+				CAST((julianday(updated_at) - 2440587.5) * 86400000 AS INTEGER)
+			FROM prices WHERE mint NOT IN (SELECT DISTINCT mint FROM price_history);",
 		)?;
+		ensure_column(&conn, "audit", "batch_id", "TEXT")?;
+		ensure_column(&conn, "prices", "version", "INTEGER NOT NULL DEFAULT 1")?;
+		ensure_column(&conn, "price_history", "version", "INTEGER NOT NULL DEFAULT 0")?;
+		ensure_column(&conn, "prices", "conf_mantissa", "TEXT")?;
+		ensure_column(&conn, "price_history", "conf_mantissa", "TEXT")?;
+		ensure_column(&conn, "price_history", "deleted", "INTEGER NOT NULL DEFAULT 0")?; // 1 on tombstones written by deletes
+		ensure_column(&conn, "config", "max_age_secs", "INTEGER")?;
+		ensure_column(&conn, "config", "max_age_overrides", "TEXT NOT NULL DEFAULT '{}'")?; // JSON object mint -> secs
+		ensure_column(&conn, "config", "circuit_breaker_bps", "INTEGER")?;
+		ensure_column(&conn, "config", "aggregation", "TEXT NOT NULL DEFAULT '{}'")?; // JSON AggregationPolicy
+		ensure_column(&conn, "config", "aggregation_overrides", "TEXT NOT NULL DEFAULT '{}'")?; // JSON object mint -> AggregationPolicy
+		ensure_column(&conn, "config", "pegs_seeded", "INTEGER NOT NULL DEFAULT 0")?; // set once env peg sources have been copied into peg_sources
+		// symbols are unique ignoring case; databases that already hold case-only duplicates keep working without the index
+		if let Err(e) = conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS idx_symbols_nocase ON symbols (symbol COLLATE NOCASE)") {
+			tracing::warn!("symbols differing only in case exist; not enforcing case-insensitive uniqueness: {}", e);
+		}
 		Ok(())
 	}
 
 	pub fn get_config(&self) -> AppResult<Config> {
 		let conn = self.conn()?;
 		let row = conn.query_row(
-			"SELECT network, version, fee_bps_default, zera_mint, supported_mints FROM config WHERE id = 1",
+			"SELECT network, version, fee_bps_default, zera_mint, supported_mints, max_age_secs, max_age_overrides, circuit_breaker_bps, aggregation, aggregation_overrides FROM config WHERE id = 1",
 			[],
 			|r| {
 				Ok(Config {
@@ -91,6 +177,11 @@ impl DbState {
 					fee_bps_default: r.get::<_, i64>(2)? as u16,
 					zera_mint: r.get(3)?,
 					supported_mints: serde_json::from_str::<Vec<String>>(&r.get::<_, String>(4)?).unwrap_or_default(),
+					max_age_secs: r.get::<_, Option<i64>>(5)?.map(|v| v as u64),
+					max_age_overrides: serde_json::from_str(&r.get::<_, String>(6)?).unwrap_or_default(),
+					circuit_breaker_bps: r.get::<_, Option<i64>>(7)?.map(|v| v as u32),
+					aggregation: serde_json::from_str(&r.get::<_, String>(8)?).unwrap_or_default(),
+					aggregation_overrides: serde_json::from_str(&r.get::<_, String>(9)?).unwrap_or_default(),
 				})
 			},
 		)?;
@@ -108,11 +199,45 @@ impl DbState {
 		if let Some(v) = patch.get("supported_mints").and_then(|v| v.as_array()) {
 			cfg.supported_mints = v.iter().filter_map(|x| x.as_str()).map(|s| s.to_string()).collect();
 		}
+		match patch.get("max_age_secs") {
+			Some(serde_json::Value::Null) => cfg.max_age_secs = None,
+			Some(v) => cfg.max_age_secs = Some(v.as_u64().ok_or_else(|| AppError::BadRequest("max_age_secs must be a non-negative integer or null".into()))?),
+			None => {}
+		}
+		match patch.get("circuit_breaker_bps") {
+			Some(serde_json::Value::Null) => cfg.circuit_breaker_bps = None,
+			Some(v) => cfg.circuit_breaker_bps = Some(v.as_u64().and_then(|v| u32::try_from(v).ok()).ok_or_else(|| AppError::BadRequest("circuit_breaker_bps must be a non-negative integer or null".into()))?),
+			None => {}
+		}
+		if let Some(v) = patch.get("max_age_overrides") {
+			cfg.max_age_overrides = serde_json::from_value(v.clone())
+				.map_err(|_| AppError::BadRequest("max_age_overrides must map mints to non-negative integers".into()))?;
+		}
+		match patch.get("aggregation") {
+			Some(serde_json::Value::Null) => cfg.aggregation = AggregationPolicy::default(),
+			Some(v) => cfg.aggregation = serde_json::from_value(v.clone()).map_err(|e| AppError::BadRequest(format!("invalid aggregation policy: {}", e)))?,
+			None => {}
+		}
+		if let Some(v) = patch.get("aggregation_overrides") {
+			cfg.aggregation_overrides = serde_json::from_value(v.clone())
+				.map_err(|e| AppError::BadRequest(format!("aggregation_overrides must map mints to aggregation policies: {}", e)))?;
+		}
 
 		let conn = self.conn()?;
 		conn.execute(
-			"UPDATE config SET network = ?, version = ?, fee_bps_default = ?, zera_mint = ?, supported_mints = ? WHERE id = 1",
-			params![cfg.network, cfg.version, cfg.fee_bps_default as i64, cfg.zera_mint, serde_json::to_string(&cfg.supported_mints)?],
+			"UPDATE config SET network = ?, version = ?, fee_bps_default = ?, zera_mint = ?, supported_mints = ?, max_age_secs = ?, max_age_overrides = ?, circuit_breaker_bps = ?, aggregation = ?, aggregation_overrides = ? WHERE id = 1",
+			params![
+				cfg.network,
+				cfg.version,
+				cfg.fee_bps_default as i64,
+				cfg.zera_mint,
+				serde_json::to_string(&cfg.supported_mints)?,
+				cfg.max_age_secs.map(|v| v as i64),
+				serde_json::to_string(&cfg.max_age_overrides)?,
+				cfg.circuit_breaker_bps.map(|v| v as i64),
+				serde_json::to_string(&cfg.aggregation)?,
+				serde_json::to_string(&cfg.aggregation_overrides)?,
+			],
 		)?;
 
 		self.insert_audit("PATCH_CONFIG", actor, "config", Some(before), Some(serde_json::to_value(&cfg)?))?;
@@ -121,96 +246,229 @@ impl DbState {
 
 	pub fn list_prices(&self) -> AppResult<Vec<Price>> {
 		let conn = self.conn()?;
-		let mut stmt = conn.prepare("SELECT mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals FROM prices ORDER BY mint")?;
-		let rows = stmt.query_map([], |r| {
-			Ok(Price {
-				mint: r.get(0)?,
-				symbol: r.get(1)?,
-				usd_mantissa: r.get(2)?,
-				usd_scale: r.get::<_, i64>(3)? as u32,
-				updated_at: r.get(4)?,
-				updated_by: r.get(5)?,
-				decimals: r.get(6)?,
-			})
-		})?;
+		let mut stmt = conn.prepare(&format!("SELECT {} FROM prices ORDER BY mint", PRICE_COLUMNS))?;
+		let rows = stmt.query_map([], map_price_row)?;
 		Ok(rows.filter_map(Result::ok).collect())
 	}
 
 	pub fn get_price(&self, mint: &str) -> AppResult<Price> {
 		let conn = self.conn()?;
-		let row = conn
-			.query_row(
-				"SELECT mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals FROM prices WHERE mint = ?",
-				params![mint],
-				|r| {
-					Ok(Price {
-						mint: r.get(0)?,
-						symbol: r.get(1)?,
-						usd_mantissa: r.get(2)?,
-						usd_scale: r.get::<_, i64>(3)? as u32,
-						updated_at: r.get(4)?,
-						updated_by: r.get(5)?,
-						decimals: r.get(6)?,
-					})
-				},
-			)
-			.optional()?;
-		row.ok_or(AppError::NotFound)
+		get_price_row(&conn, mint)?.ok_or(AppError::NotFound)
 	}
 
-	pub fn upsert_price(&self, price: &Price, actor: &str) -> AppResult<Price> {
+	/// Unless `force` is set, the write is refused for frozen mints and trips the circuit breaker on oversized moves.
+	pub fn upsert_price(&self, price: &Price, actor: &str, force: bool) -> AppResult<Price> {
 		let conn = self.conn()?;
-		let before = self
-			.get_price(&price.mint)
-			.ok()
-			.and_then(|p| serde_json::to_value(p).ok());
+		let result = write_price(&conn, price, actor, None, force);
+		self.trip_breaker(result)
+	}
 
-		conn.execute(
-			"INSERT INTO prices (mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals) VALUES (?, ?, ?, ?, ?, ?, ?)
-			ON CONFLICT(mint) DO UPDATE SET symbol = excluded.symbol, usd_mantissa = excluded.usd_mantissa, usd_scale = excluded.usd_scale, updated_at = excluded.updated_at, updated_by = excluded.updated_by, decimals = excluded.decimals",
-			params![
-				price.mint,
-				price.symbol.clone(),
-				price.usd_mantissa,
-				price.usd_scale as i64,
-				price.updated_at,
-				price.updated_by,
-				price.decimals.map(|d| d as i64)
-			],
+	/// Writes every price in one transaction; either all rows land or none do.
+	/// Audit entries share the returned batch id.
+	pub fn upsert_prices_batch(&self, prices: &[Price], actor: &str, force: bool) -> AppResult<(String, Vec<Price>)> {
+		let batch_id = Uuid::new_v4().to_string();
+		let result = (|| {
+			let mut conn = self.conn()?;
+			let tx = conn.transaction()?;
+			let mut saved = Vec::with_capacity(prices.len());
+			for price in prices {
+				saved.push(write_price(&tx, price, actor, Some(&batch_id), force)?);
+			}
+			tx.commit()?;
+			Ok(saved)
+		})();
+		Ok((batch_id, self.trip_breaker(result)?))
+	}
+
+	/// Writes an aggregated price and the source readings behind it in one transaction.
+	pub fn upsert_aggregated_price(&self, price: &Price, actor: &str, readings: &[SourceReading]) -> AppResult<Price> {
+		let result = (|| {
+			let mut conn = self.conn()?;
+			let tx = conn.transaction()?;
+			let saved = write_price(&tx, price, actor, None, false)?;
+			for r in readings {
+				tx.execute(
+					"INSERT INTO source_readings (mint, price_version, source, kind, usd_mantissa, usd_scale, weight, read_at, status, deviation_bps)
+					VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+					params![
+						saved.mint,
+						saved.version as i64,
+						r.source,
+						r.kind,
+						r.usd_mantissa,
+						r.usd_scale as i64,
+						r.weight as i64,
+						r.read_at,
+						r.status,
+						r.deviation_bps.map(|v| v as i64),
+					],
+				)?;
+			}
+			tx.commit()?;
+			Ok(saved)
+		})();
+		self.trip_breaker(result)
+	}
+
+	/// Readings behind the latest aggregated write of `mint` at or before `version`, with that write's version.
+	pub fn list_source_readings(&self, mint: &str, version: Option<u64>) -> AppResult<(Option<u64>, Vec<SourceReading>)> {
+		let conn = self.conn()?;
+		let found: Option<i64> = conn.query_row(
+			"SELECT MAX(price_version) FROM source_readings WHERE mint = ? AND (? IS NULL OR price_version <= ?)",
+			params![mint, version.map(|v| v as i64), version.map(|v| v as i64)],
+			|r| r.get(0),
+		)?;
+		let Some(found) = found else { return Ok((None, Vec::new())); };
+		let mut stmt = conn.prepare(
+			"SELECT source, kind, usd_mantissa, usd_scale, weight, read_at, status, deviation_bps FROM source_readings
+			WHERE mint = ? AND price_version = ? ORDER BY id",
 		)?;
+		let rows = stmt.query_map(params![mint, found], |r| {
+			Ok(SourceReading {
+				source: r.get(0)?,
+				kind: r.get(1)?,
+				usd_mantissa: r.get(2)?,
+				usd_scale: r.get::<_, i64>(3)? as u32,
+				weight: r.get::<_, i64>(4)? as u32,
+				read_at: r.get(5)?,
+				status: r.get(6)?,
+				deviation_bps: r.get::<_, Option<i64>>(7)?.map(|v| v as u64),
+			})
+		})?;
+		let mut out = Vec::new();
+		for r in rows { out.push(r?); }
+		Ok((Some(found as u64), out))
+	}
 
-		self.insert_audit("UPSERT_PRICE", actor, &price.mint, before, Some(serde_json::to_value(price)?))?;
-		self.get_price(&price.mint)
+	/// Freezes the mint named by a `CircuitBreaker` error; done outside the failed write so a rollback keeps the freeze.
+	fn trip_breaker<T>(&self, result: AppResult<T>) -> AppResult<T> {
+		if let Err(AppError::CircuitBreaker { mint, move_bps, limit_bps }) = &result {
+			let reason = format!("circuit breaker: single update moved {} bps (limit {})", move_bps, limit_bps);
+			self.freeze_mint(mint, &reason, "circuit_breaker", true)?;
+		}
+		result
 	}
 
-	pub fn patch_price(&self, mint: &str, patch: serde_json::Value, actor: &str) -> AppResult<Price> {
+	/// Applies `patch` to the stored price. With `expected_version` set the write only lands if the
+	/// stored version still matches, otherwise `Conflict` is returned.
+	pub fn patch_price(&self, mint: &str, patch: serde_json::Value, actor: &str, expected_version: Option<u64>, force: bool) -> AppResult<Price> {
 		let before = self.get_price(mint)?;
+		if let Some(expected) = expected_version.filter(|v| *v != before.version) {
+			return Err(AppError::Conflict(format!("version mismatch for {}: expected {}, current {}", mint, expected, before.version)));
+		}
 		let mut price = before.clone();
 		if let Some(v) = patch.get("symbol").and_then(|v| v.as_str()) { price.symbol = Some(v.to_string()); }
 		if let Some(v) = patch.get("usd_mantissa").and_then(|v| v.as_str()) { price.usd_mantissa = v.to_string(); }
 		if let Some(v) = patch.get("usd_scale").and_then(|v| v.as_u64()) { price.usd_scale = v as u32; }
 		if let Some(v) = patch.get("decimals").and_then(|v| v.as_u64()) { price.decimals = Some(v as u8); }
+		match patch.get("conf_mantissa") {
+			Some(serde_json::Value::Null) => price.conf_mantissa = None,
+			Some(v) => price.conf_mantissa = Some(v.as_str().ok_or_else(|| AppError::BadRequest("conf_mantissa must be a string or null".into()))?.to_string()),
+			// an unpatched confidence follows a scale change, widening rather than narrowing
+			None if price.usd_scale != before.usd_scale => {
+				if let Some(conf) = before.conf_amount()? {
+					price.conf_mantissa = Some(conf.rescale(price.usd_scale, Rounding::Ceil)?.mantissa().to_string());
+				}
+			}
+			None => {}
+		}
+		price.set_usd_amount(price.usd_amount()?);
+		price.conf_amount()?;
 		price.updated_at = Price::now_iso();
 		price.updated_by = format!("admin:{}", actor);
+		price.version = before.version + 1;
 
-		let conn = self.conn()?;
-		conn.execute(
-			"UPDATE prices SET symbol = ?, usd_mantissa = ?, usd_scale = ?, updated_at = ?, updated_by = ?, decimals = ? WHERE mint = ?",
-			params![
-				price.symbol.clone(), price.usd_mantissa, price.usd_scale as i64, price.updated_at, price.updated_by, price.decimals.map(|d| d as i64), price.mint
-			],
+		let result = (|| {
+			let mut conn = self.conn()?;
+			let tx = conn.transaction()?;
+			guard_write(&tx, Some(&before), &price, force)?;
+			// compare-and-swap on the version read above so a concurrent write in between is not overwritten
+			let n = tx.execute(
+				"UPDATE prices SET symbol = ?, usd_mantissa = ?, usd_scale = ?, conf_mantissa = ?, updated_at = ?, updated_by = ?, decimals = ?, version = version + 1 WHERE mint = ? AND version = ?",
+				params![
+					price.symbol.clone(), price.usd_mantissa, price.usd_scale as i64, price.conf_mantissa.clone(), price.updated_at, price.updated_by, price.decimals.map(|d| d as i64), price.mint, before.version as i64
+				],
+			)?;
+			if n == 0 { return Err(version_conflict(&tx, mint, expected_version.unwrap_or(before.version))); }
+			insert_price_history(&tx, &price)?;
+			sync_symbol(&tx, mint, before.symbol.as_deref(), price.symbol.as_deref(), actor)?;
+			insert_audit_entry(&tx, &AuditEntry::new("UPSERT_PRICE", actor, mint, Some(serde_json::to_value(&before)?), Some(serde_json::to_value(&price)?)))?;
+			tx.commit()?;
+			Ok(price)
+		})();
+		self.trip_breaker(result)
+	}
+
+	/// Deletes the price and records a tombstone in `price_history`, so point-in-time lookups past the
+	/// delete find no price.
+	pub fn delete_price(&self, mint: &str, actor: &str, expected_version: Option<u64>, force: bool) -> AppResult<()> {
+		let mut conn = self.conn()?;
+		let tx = conn.transaction()?;
+		if !force && get_frozen_row(&tx, mint)?.is_some() { return Err(frozen_conflict(mint)); }
+		let before = tx
+			.query_row(&format!("SELECT {} FROM prices WHERE mint = ?", PRICE_COLUMNS), params![mint], map_price_row)
+			.optional()?;
+		let n = tx.execute(
+			"DELETE FROM prices WHERE mint = ? AND (?2 IS NULL OR version = ?2)",
+			params![mint, expected_version.map(|v| v as i64)],
 		)?;
-		self.insert_audit("UPSERT_PRICE", actor, mint, Some(serde_json::to_value(before)?), Some(serde_json::to_value(&price)?))?;
-		Ok(price)
+		let Some(before) = before.filter(|_| n > 0) else {
+			return Err(match expected_version {
+				Some(expected) => version_conflict(&tx, mint, expected),
+				None => AppError::NotFound,
+			});
+		};
+		let tombstone = Price { updated_at: Price::now_iso(), updated_by: actor.to_string(), ..before.clone() };
+		insert_price_history_row(&tx, &tombstone, true)?;
+		insert_audit_entry(&tx, &AuditEntry::new("DELETE_PRICE", actor, mint, Some(serde_json::to_value(&before)?), None))?;
+		tx.commit()?;
+		Ok(())
 	}
 
-	pub fn delete_price(&self, mint: &str, actor: &str) -> AppResult<()> {
-		let before = self.get_price(mint).ok().and_then(|p| serde_json::to_value(p).ok());
+	/// Price snapshots for `mint` in write order, optionally bounded by `[from_ms, to_ms]`.
+	/// The cursor is the history row id of the last entry of the previous page.
+	pub fn list_price_history(&self, mint: &str, from_ms: Option<i64>, to_ms: Option<i64>, limit: usize, cursor: Option<i64>) -> AppResult<(Vec<Price>, Option<String>)> {
 		let conn = self.conn()?;
-		let n = conn.execute("DELETE FROM prices WHERE mint = ?", params![mint])?;
-		if n == 0 { return Err(AppError::NotFound); }
-		self.insert_audit("DELETE_PRICE", actor, mint, before, None)?;
-		Ok(())
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {}, id FROM price_history
+			WHERE mint = ?1 AND deleted = 0 AND (?2 IS NULL OR ts_ms >= ?2) AND (?3 IS NULL OR ts_ms <= ?3) AND (?4 IS NULL OR id > ?4)
+			ORDER BY id ASC LIMIT ?5",
+			PRICE_COLUMNS
+		))?;
+		let rows = stmt.query_map(params![mint, from_ms, to_ms, cursor, limit as i64], |r| {
+			Ok((r.get::<_, i64>(PRICE_COLUMN_COUNT)?, map_price_row(r)?))
+		})?;
+		let rows: Vec<(i64, Price)> = rows.filter_map(Result::ok).collect();
+		let next_cursor = if rows.len() == limit { rows.last().map(|(id, _)| id.to_string()) } else { None };
+		Ok((rows.into_iter().map(|(_, p)| p).collect(), next_cursor))
+	}
+
+	/// All history rows for `mint` inside `[from_ms, to_ms]`, oldest first, paired with their unix-millis timestamp.
+	pub fn price_history_points(&self, mint: &str, from_ms: i64, to_ms: i64, max_rows: usize) -> AppResult<Vec<(i64, Price)>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {}, ts_ms FROM price_history
+			WHERE mint = ? AND deleted = 0 AND ts_ms >= ? AND ts_ms <= ? ORDER BY ts_ms ASC, id ASC LIMIT ?",
+			PRICE_COLUMNS
+		))?;
+		let rows = stmt.query_map(params![mint, from_ms, to_ms, max_rows as i64], |r| {
+			Ok((r.get::<_, i64>(PRICE_COLUMN_COUNT)?, map_price_row(r)?))
+		})?;
+		Ok(rows.filter_map(Result::ok).collect())
+	}
+
+	/// Latest recorded price for `mint` whose `updated_at` is at or before `ts_ms`; `None` if there is
+	/// none or the price had been deleted by then.
+	pub fn get_price_at(&self, mint: &str, ts_ms: i64) -> AppResult<Option<Price>> {
+		let conn = self.conn()?;
+		let row = conn
+			.query_row(
+				&format!("SELECT {}, deleted FROM price_history WHERE mint = ? AND ts_ms <= ? ORDER BY ts_ms DESC, id DESC LIMIT 1", PRICE_COLUMNS),
+				params![mint, ts_ms],
+				|r| Ok((map_price_row(r)?, r.get::<_, bool>(PRICE_COLUMN_COUNT)?)),
+			)
+			.optional()?;
+		Ok(row.and_then(|(price, deleted)| (!deleted).then_some(price)))
 	}
 
 	pub fn list_symbols(&self) -> AppResult<Vec<SymbolMap>> {
@@ -220,13 +478,50 @@ impl DbState {
 		Ok(rows.filter_map(Result::ok).collect())
 	}
 
-	pub fn upsert_symbol(&self, symbol: &str, mint: &str) -> AppResult<()> {
+	/// Accepts a mint or `symbol:<SYMBOL>` and returns the mint; symbols match case-insensitively.
+	pub fn resolve_mint(&self, key: &str) -> AppResult<String> {
+		match key.strip_prefix("symbol:") {
+			Some(symbol) => self.mint_for_symbol(symbol),
+			None => Ok(key.to_string()),
+		}
+	}
+
+	pub fn mint_for_symbol(&self, symbol: &str) -> AppResult<String> {
+		self.list_symbols()?
+			.into_iter()
+			.find(|s| s.symbol.eq_ignore_ascii_case(symbol.trim()))
+			.map(|s| s.mint)
+			.ok_or_else(|| AppError::NoPrice(format!("unknown symbol {}", symbol)))
+	}
+
+	pub fn get_symbol(&self, symbol: &str) -> AppResult<SymbolMap> {
 		let conn = self.conn()?;
-		conn.execute(
-			"INSERT INTO symbols (symbol, mint) VALUES (?, ?) ON CONFLICT(symbol) DO UPDATE SET mint = excluded.mint",
-			params![symbol, mint],
-		)?;
-		Ok(())
+		get_symbol_row(&conn, symbol)?.ok_or(AppError::NotFound)
+	}
+
+	pub fn symbols_for_mint(&self, mint: &str) -> AppResult<Vec<SymbolMap>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare("SELECT symbol, mint FROM symbols WHERE mint = ? ORDER BY symbol")?;
+		let rows = stmt.query_map(params![mint], |r| Ok(SymbolMap { symbol: r.get(0)?, mint: r.get(1)? }))?;
+		Ok(rows.filter_map(Result::ok).collect())
+	}
+
+	pub fn upsert_symbol(&self, symbol: &str, mint: &str, actor: &str) -> AppResult<SymbolMap> {
+		let conn = self.conn()?;
+		upsert_symbol_row(&conn, symbol, mint, actor)
+	}
+
+	/// Removes a mapping (matched case-insensitively) and returns what was deleted. The mint's price
+	/// drops the symbol too, so later writes that keep the stored symbol don't bring the mapping back.
+	pub fn delete_symbol(&self, symbol: &str, actor: &str) -> AppResult<SymbolMap> {
+		let mut conn = self.conn()?;
+		let tx = conn.transaction()?;
+		let before = get_symbol_row(&tx, symbol)?.ok_or(AppError::NotFound)?;
+		tx.execute("DELETE FROM symbols WHERE symbol = ?", params![before.symbol])?;
+		tx.execute("UPDATE prices SET symbol = NULL WHERE mint = ? AND symbol = ? COLLATE NOCASE", params![before.mint, before.symbol])?;
+		insert_audit_entry(&tx, &AuditEntry::new("DELETE_SYMBOL", actor, &before.symbol, Some(serde_json::to_value(&before)?), None))?;
+		tx.commit()?;
+		Ok(before)
 	}
 
 	pub fn insert_audit(
@@ -245,27 +540,15 @@ impl DbState {
 			after,
 		);
 		let conn = self.conn()?;
-		conn.execute(
-			"INSERT INTO audit (id, ts, actor, action, target, before, after) VALUES (?, ?, ?, ?, ?, ?, ?)",
-			params![
-				entry.id,
-				entry.ts,
-				entry.actor,
-				entry.action,
-				entry.target,
-				entry.before.map(|v| v.to_string()),
-				entry.after.map(|v| v.to_string()),
-			],
-		)?;
-		Ok(())
+		insert_audit_entry(&conn, &entry)
 	}
 
 	pub fn list_audit(&self, limit: usize, cursor: Option<String>) -> AppResult<(Vec<AuditEntry>, Option<String>)> {
 		let conn = self.conn()?;
 		let mut stmt = if cursor.is_some() {
-			conn.prepare("SELECT id, ts, actor, action, target, before, after FROM audit WHERE id < ? ORDER BY id DESC LIMIT ?")?
+			conn.prepare("SELECT id, ts, actor, action, target, before, after, batch_id FROM audit WHERE id < ? ORDER BY id DESC LIMIT ?")?
 		} else {
-			conn.prepare("SELECT id, ts, actor, action, target, before, after FROM audit ORDER BY id DESC LIMIT ?")?
+			conn.prepare("SELECT id, ts, actor, action, target, before, after, batch_id FROM audit ORDER BY id DESC LIMIT ?")?
 		};
 		let rows = if let Some(c) = cursor {
 			stmt.query_map(params![c, limit as i64], map_audit_row)?
@@ -281,6 +564,184 @@ impl DbState {
 	}
 }
 
+// ================= Frozen mints =================
+impl DbState {
+	/// Freezes `mint`, replacing any existing freeze.
+	pub fn freeze_mint(&self, mint: &str, reason: &str, actor: &str, auto: bool) -> AppResult<FrozenMint> {
+		let conn = self.conn()?;
+		let before = get_frozen_row(&conn, mint)?;
+		let frozen = FrozenMint { mint: mint.to_string(), reason: reason.to_string(), frozen_at: Price::now_iso(), frozen_by: actor.to_string(), auto };
+		conn.execute(
+			"INSERT OR REPLACE INTO frozen_mints (mint, reason, frozen_at, frozen_by, auto) VALUES (?, ?, ?, ?, ?)",
+			params![frozen.mint, frozen.reason, frozen.frozen_at, frozen.frozen_by, auto as i64],
+		)?;
+		insert_audit_entry(&conn, &AuditEntry::new("FREEZE_MINT", actor, mint, before.map(serde_json::to_value).transpose()?, Some(serde_json::to_value(&frozen)?)))?;
+		Ok(frozen)
+	}
+
+	pub fn unfreeze_mint(&self, mint: &str, actor: &str) -> AppResult<FrozenMint> {
+		let conn = self.conn()?;
+		let before = get_frozen_row(&conn, mint)?.ok_or(AppError::NotFound)?;
+		conn.execute("DELETE FROM frozen_mints WHERE mint = ?", params![mint])?;
+		insert_audit_entry(&conn, &AuditEntry::new("UNFREEZE_MINT", actor, mint, Some(serde_json::to_value(&before)?), None))?;
+		Ok(before)
+	}
+
+	pub fn get_frozen(&self, mint: &str) -> AppResult<Option<FrozenMint>> {
+		let conn = self.conn()?;
+		get_frozen_row(&conn, mint)
+	}
+
+	pub fn is_frozen(&self, mint: &str) -> bool {
+		self.get_frozen(mint).map(|f| f.is_some()).unwrap_or(false)
+	}
+
+	pub fn list_frozen(&self) -> AppResult<Vec<FrozenMint>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare("SELECT mint, reason, frozen_at, frozen_by, auto FROM frozen_mints ORDER BY mint")?;
+		let rows = stmt.query_map([], map_frozen_row)?;
+		let mut out = Vec::new();
+		for r in rows { out.push(r?); }
+		Ok(out)
+	}
+}
+
+// ================= Scheduled prices =================
+const SCHEDULE_COLUMNS: &str = "id, mint, symbol, usd_mantissa, usd_scale, decimals, run_at, status, created_at, created_by, applied_at, error";
+
+impl DbState {
+	pub fn insert_schedule(&self, schedule: &ScheduledPrice, actor: &str) -> AppResult<ScheduledPrice> {
+		let run_at_ms = parse_rfc3339_ms(&schedule.run_at).ok_or_else(|| AppError::BadRequest("run_at must be an RFC3339 timestamp".into()))?;
+		let conn = self.conn()?;
+		conn.execute(
+			"INSERT INTO scheduled_prices (id, mint, symbol, usd_mantissa, usd_scale, decimals, run_at, run_at_ms, status, created_at, created_by)
+			VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'pending', ?, ?)",
+			params![
+				schedule.id,
+				schedule.mint,
+				schedule.symbol,
+				schedule.usd_mantissa,
+				schedule.usd_scale as i64,
+				schedule.decimals.map(|d| d as i64),
+				schedule.run_at,
+				run_at_ms,
+				schedule.created_at,
+				schedule.created_by,
+			],
+		)?;
+		let saved = get_schedule_row(&conn, &schedule.id)?.ok_or(AppError::NotFound)?;
+		insert_audit_entry(&conn, &AuditEntry::new("CREATE_SCHEDULE", actor, &saved.mint, None, Some(serde_json::to_value(&saved)?)))?;
+		Ok(saved)
+	}
+
+	/// Schedules ordered by due time, optionally narrowed to one mint and/or status.
+	pub fn list_schedules(&self, mint: Option<&str>, status: Option<&str>) -> AppResult<Vec<ScheduledPrice>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {} FROM scheduled_prices WHERE (?1 IS NULL OR mint = ?1) AND (?2 IS NULL OR status = ?2) ORDER BY run_at_ms, id",
+			SCHEDULE_COLUMNS
+		))?;
+		let rows = stmt.query_map(params![mint, status], map_schedule_row)?;
+		let mut out = Vec::new();
+		for r in rows { out.push(r?); }
+		Ok(out)
+	}
+
+	pub fn delete_schedule(&self, id: &str, actor: &str) -> AppResult<ScheduledPrice> {
+		let conn = self.conn()?;
+		let before = get_schedule_row(&conn, id)?.ok_or(AppError::NotFound)?;
+		conn.execute("DELETE FROM scheduled_prices WHERE id = ?", params![id])?;
+		insert_audit_entry(&conn, &AuditEntry::new("DELETE_SCHEDULE", actor, &before.mint, Some(serde_json::to_value(&before)?), None))?;
+		Ok(before)
+	}
+
+	/// Pending schedules whose `run_at` is at or before `now_ms`, oldest first.
+	pub fn due_schedules(&self, now_ms: i64) -> AppResult<Vec<ScheduledPrice>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare(&format!(
+			"SELECT {} FROM scheduled_prices WHERE status = 'pending' AND run_at_ms <= ? ORDER BY run_at_ms, id",
+			SCHEDULE_COLUMNS
+		))?;
+		let rows = stmt.query_map(params![now_ms], map_schedule_row)?;
+		let mut out = Vec::new();
+		for r in rows { out.push(r?); }
+		Ok(out)
+	}
+
+	/// Marks a pending schedule `applied`, or `failed` with `error`.
+	pub fn finish_schedule(&self, id: &str, error: Option<&str>) -> AppResult<()> {
+		let conn = self.conn()?;
+		conn.execute(
+			"UPDATE scheduled_prices SET status = ?, applied_at = ?, error = ? WHERE id = ? AND status = 'pending'",
+			params![if error.is_some() { "failed" } else { "applied" }, Price::now_iso(), error, id],
+		)?;
+		Ok(())
+	}
+}
+
+// ================= Peg sources =================
+const PEG_COLUMNS: &str = "id, spec, created_at, created_by, updated_at, updated_by";
+
+impl DbState {
+	pub fn insert_peg(&self, spec: &SourceSpec, actor: &str) -> AppResult<PegSource> {
+		let conn = self.conn()?;
+		let saved = insert_peg_row(&conn, spec, actor)?;
+		insert_audit_entry(&conn, &AuditEntry::new("CREATE_PEG", actor, &saved.spec.mint, None, Some(serde_json::to_value(&saved)?)))?;
+		Ok(saved)
+	}
+
+	pub fn update_peg(&self, id: &str, spec: &SourceSpec, actor: &str) -> AppResult<PegSource> {
+		let conn = self.conn()?;
+		let before = get_peg_row(&conn, id)?.ok_or(AppError::NotFound)?;
+		conn.execute(
+			"UPDATE peg_sources SET mint = ?, spec = ?, updated_at = ?, updated_by = ? WHERE id = ?",
+			params![spec.mint, serde_json::to_string(spec)?, Price::now_iso(), actor, id],
+		)?;
+		let saved = get_peg_row(&conn, id)?.ok_or(AppError::NotFound)?;
+		insert_audit_entry(&conn, &AuditEntry::new("UPDATE_PEG", actor, &saved.spec.mint, Some(serde_json::to_value(&before)?), Some(serde_json::to_value(&saved)?)))?;
+		Ok(saved)
+	}
+
+	pub fn delete_peg(&self, id: &str, actor: &str) -> AppResult<PegSource> {
+		let conn = self.conn()?;
+		let before = get_peg_row(&conn, id)?.ok_or(AppError::NotFound)?;
+		conn.execute("DELETE FROM peg_sources WHERE id = ?", params![id])?;
+		insert_audit_entry(&conn, &AuditEntry::new("DELETE_PEG", actor, &before.spec.mint, Some(serde_json::to_value(&before)?), None))?;
+		Ok(before)
+	}
+
+	pub fn get_peg(&self, id: &str) -> AppResult<PegSource> {
+		let conn = self.conn()?;
+		get_peg_row(&conn, id)?.ok_or(AppError::NotFound)
+	}
+
+	/// Pegs in creation order, optionally narrowed to one mint.
+	pub fn list_pegs(&self, mint: Option<&str>) -> AppResult<Vec<PegSource>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare(&format!("SELECT {} FROM peg_sources WHERE (?1 IS NULL OR mint = ?1) ORDER BY created_at, rowid", PEG_COLUMNS))?;
+		let rows = stmt.query_map(params![mint], map_peg_row)?;
+		let mut out = Vec::new();
+		for r in rows { out.push(r?); }
+		Ok(out)
+	}
+
+	/// Copies `specs` into `peg_sources` the first time it is called on this database; later calls do
+	/// nothing, so pegs deleted at runtime stay deleted across restarts. Returns how many were inserted.
+	pub fn seed_pegs(&self, specs: &[SourceSpec]) -> AppResult<usize> {
+		let mut conn = self.conn()?;
+		let tx = conn.transaction()?;
+		let seeded: i64 = tx.query_row("SELECT pegs_seeded FROM config WHERE id = 1", [], |r| r.get(0))?;
+		if seeded != 0 { return Ok(0); }
+		for spec in specs {
+			let saved = insert_peg_row(&tx, spec, "seed")?;
+			insert_audit_entry(&tx, &AuditEntry::new("CREATE_PEG", "seed", &saved.spec.mint, None, Some(serde_json::to_value(&saved)?)))?;
+		}
+		tx.execute("UPDATE config SET pegs_seeded = 1 WHERE id = 1", [])?;
+		tx.commit()?;
+		Ok(specs.len())
+	}
+}
+
 // ================= L2 HTTP cache helpers =================
 impl DbState {
 	pub fn http_cache_get(&self, cache_key: &str, now_epoch: i64) -> AppResult<Option<(u16, String, i64)>> {
@@ -330,7 +791,199 @@ impl DbState {
 	}
 }
 
-fn row_to_audit(r: &r2d2_sqlite::rusqlite::Row<'_>) -> AuditEntry {
+const PRICE_COLUMNS: &str = "mint, symbol, usd_mantissa, usd_scale, updated_at, updated_by, decimals, version, conf_mantissa";
+/// Index of the first column selected after `PRICE_COLUMNS`.
+const PRICE_COLUMN_COUNT: usize = 9;
+
+fn map_price_row(r: &Row<'_>) -> Result<Price, r2d2_sqlite::rusqlite::Error> {
+	Ok(Price {
+		mint: r.get(0)?,
+		symbol: r.get(1)?,
+		usd_mantissa: r.get(2)?,
+		usd_scale: r.get::<_, i64>(3)? as u32,
+		updated_at: r.get(4)?,
+		updated_by: r.get(5)?,
+		decimals: r.get(6)?,
+		version: r.get::<_, i64>(7)? as u64,
+		conf_mantissa: r.get(8)?,
+		age_secs: None,
+		stale: None,
+	})
+}
+
+fn get_price_row(conn: &Connection, mint: &str) -> AppResult<Option<Price>> {
+	let row = conn
+		.query_row(&format!("SELECT {} FROM prices WHERE mint = ?", PRICE_COLUMNS), params![mint], map_price_row)
+		.optional()?;
+	Ok(row)
+}
+
+/// Validates and stores `price` on `conn` (which may be a transaction), recording history, symbol sync and audit.
+fn write_price(conn: &Connection, price: &Price, actor: &str, batch_id: Option<&str>, force: bool) -> AppResult<Price> {
+	// lookup keys are resolved by the caller; storing one would shadow the symbol it names
+	if price.mint.starts_with("symbol:") { return Err(AppError::BadRequest(format!("{} is a lookup key, not a mint", price.mint))); }
+	let mut price = price.clone();
+	price.set_usd_amount(price.usd_amount()?);
+	price.conf_amount()?;
+	let before_price = get_price_row(conn, &price.mint)?;
+	guard_write(conn, before_price.as_ref(), &price, force)?;
+	let before = before_price.as_ref().map(serde_json::to_value).transpose()?;
+
+	conn.execute(
+		"INSERT INTO prices (mint, symbol, usd_mantissa, usd_scale, conf_mantissa, updated_at, updated_by, decimals, version)
+		VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, COALESCE((SELECT MAX(version) FROM price_history WHERE mint = ?1), 0) + 1)
+		ON CONFLICT(mint) DO UPDATE SET symbol = excluded.symbol, usd_mantissa = excluded.usd_mantissa, usd_scale = excluded.usd_scale, conf_mantissa = excluded.conf_mantissa, updated_at = excluded.updated_at, updated_by = excluded.updated_by, decimals = excluded.decimals, version = prices.version + 1",
+		params![
+			price.mint,
+			price.symbol.clone(),
+			price.usd_mantissa,
+			price.usd_scale as i64,
+			price.conf_mantissa.clone(),
+			price.updated_at,
+			price.updated_by,
+			price.decimals.map(|d| d as i64)
+		],
+	)?;
+	let saved = get_price_row(conn, &price.mint)?.ok_or(AppError::NotFound)?;
+	insert_price_history(conn, &saved)?;
+	sync_symbol(conn, &saved.mint, before_price.as_ref().and_then(|p| p.symbol.as_deref()), saved.symbol.as_deref(), actor)?;
+
+	let mut entry = AuditEntry::new("UPSERT_PRICE", actor, &saved.mint, before, Some(serde_json::to_value(&saved)?));
+	entry.batch_id = batch_id.map(str::to_string);
+	insert_audit_entry(conn, &entry)?;
+	Ok(saved)
+}
+
+/// Refuses unforced writes to frozen mints, and returns `CircuitBreaker` when the move from `before`
+/// exceeds the configured limit. The caller is responsible for freezing the mint.
+fn guard_write(conn: &Connection, before: Option<&Price>, price: &Price, force: bool) -> AppResult<()> {
+	if force { return Ok(()); }
+	if get_frozen_row(conn, &price.mint)?.is_some() { return Err(frozen_conflict(&price.mint)); }
+	let limit: Option<i64> = conn.query_row("SELECT circuit_breaker_bps FROM config WHERE id = 1", [], |r| r.get(0))?;
+	let (Some(limit), Some(before)) = (limit, before) else { return Ok(()) };
+	let Ok(old) = before.usd_amount() else { return Ok(()) };
+	if let Some(move_bps) = old.change_bps(&price.usd_amount()?) {
+		if move_bps > limit as u128 {
+			return Err(AppError::CircuitBreaker { mint: price.mint.clone(), move_bps, limit_bps: limit as u32 });
+		}
+	}
+	Ok(())
+}
+
+fn frozen_conflict(mint: &str) -> AppError {
+	AppError::Conflict(format!("{} is frozen; pass force=true to override", mint))
+}
+
+/// `Conflict` when the row exists but its version differs from `expected`, `NotFound` when it is gone.
+fn version_conflict(conn: &Connection, mint: &str, expected: u64) -> AppError {
+	match get_price_row(conn, mint) {
+		Ok(Some(current)) => AppError::Conflict(format!("version mismatch for {}: expected {}, current {}", mint, expected, current.version)),
+		Ok(None) => AppError::NotFound,
+		Err(e) => e,
+	}
+}
+
+/// Adds `column` to `table` on databases created before the column existed.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> AppResult<()> {
+	let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+	let exists = stmt.query_map([], |r| r.get::<_, String>(1))?.filter_map(Result::ok).any(|c| c == column);
+	if !exists {
+		conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+	}
+	Ok(())
+}
+
+fn insert_price_history(conn: &Connection, price: &Price) -> AppResult<()> {
+	insert_price_history_row(conn, price, false)
+}
+
+/// A tombstone (`deleted`) row marks the point where `price` stopped existing.
+fn insert_price_history_row(conn: &Connection, price: &Price, deleted: bool) -> AppResult<()> {
+	conn.execute(
+		"INSERT INTO price_history (mint, symbol, usd_mantissa, usd_scale, conf_mantissa, updated_at, updated_by, decimals, version, ts_ms, deleted) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		params![
+			price.mint,
+			price.symbol.clone(),
+			price.usd_mantissa,
+			price.usd_scale as i64,
+			price.conf_mantissa.clone(),
+			price.updated_at,
+			price.updated_by,
+			price.decimals.map(|d| d as i64),
+			price.version as i64,
+			price.updated_at_ms(),
+			deleted
+		],
+	)?;
+	Ok(())
+}
+
+/// Keeps `symbols` in step with a price's `symbol`: when it changes, points `new` at `mint` and drops `old`
+/// if it still maps to `mint`. A price written without a symbol, or with the one it already had, leaves
+/// existing mappings alone.
+fn sync_symbol(conn: &Connection, mint: &str, old: Option<&str>, new: Option<&str>, actor: &str) -> AppResult<()> {
+	let Some(new) = new.map(str::trim).filter(|s| !s.is_empty()) else { return Ok(()) };
+	if old.map(|o| o.trim().eq_ignore_ascii_case(new)).unwrap_or(false) { return Ok(()); }
+	if let Some(old) = old {
+		let stale = SymbolMap { symbol: old.to_string(), mint: mint.to_string() };
+		if conn.execute("DELETE FROM symbols WHERE symbol = ? AND mint = ?", params![stale.symbol, stale.mint])? > 0 {
+			insert_audit_entry(conn, &AuditEntry::new("DELETE_SYMBOL", actor, &stale.symbol, Some(serde_json::to_value(&stale)?), None))?;
+		}
+	}
+	upsert_symbol_row(conn, new, mint, actor)?;
+	Ok(())
+}
+
+fn insert_audit_entry(conn: &Connection, entry: &AuditEntry) -> AppResult<()> {
+	conn.execute(
+		"INSERT INTO audit (id, ts, actor, action, target, before, after, batch_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+		params![
+			entry.id,
+			entry.ts,
+			entry.actor,
+			entry.action,
+			entry.target,
+			entry.before.as_ref().map(|v| v.to_string()),
+			entry.after.as_ref().map(|v| v.to_string()),
+			entry.batch_id,
+		],
+	)?;
+	Ok(())
+}
+
+fn get_symbol_row(conn: &Connection, symbol: &str) -> AppResult<Option<SymbolMap>> {
+	let row = conn
+		.query_row(
+			"SELECT symbol, mint FROM symbols WHERE symbol = ? COLLATE NOCASE ORDER BY symbol = ? DESC LIMIT 1",
+			params![symbol.trim(), symbol.trim()],
+			|r| Ok(SymbolMap { symbol: r.get(0)?, mint: r.get(1)? }),
+		)
+		.optional()?;
+	Ok(row)
+}
+
+/// Points `symbol` at `mint`. Symbols are unique ignoring case: re-pointing needs the stored spelling,
+/// and a differently cased symbol that maps elsewhere is a `Conflict`.
+fn upsert_symbol_row(conn: &Connection, symbol: &str, mint: &str, actor: &str) -> AppResult<SymbolMap> {
+	let after = SymbolMap { symbol: symbol.trim().to_string(), mint: mint.to_string() };
+	if after.symbol.is_empty() { return Err(AppError::BadRequest("symbol must not be empty".into())); }
+	let before = get_symbol_row(conn, &after.symbol)?;
+	if let Some(before) = &before {
+		if before.mint == after.mint { return Ok(before.clone()); }
+		if before.symbol != after.symbol {
+			return Err(AppError::Conflict(format!("symbol {} already maps {} as {}", after.symbol, before.mint, before.symbol)));
+		}
+	}
+	conn.execute(
+		"INSERT INTO symbols (symbol, mint) VALUES (?, ?) ON CONFLICT(symbol) DO UPDATE SET mint = excluded.mint",
+		params![after.symbol, after.mint],
+	)?;
+	let before = before.map(serde_json::to_value).transpose()?;
+	insert_audit_entry(conn, &AuditEntry::new("UPSERT_SYMBOL", actor, &after.symbol, before, Some(serde_json::to_value(&after)?)))?;
+	Ok(after)
+}
+
+fn row_to_audit(r: &Row<'_>) -> AuditEntry {
 	AuditEntry {
 		id: r.get(0).unwrap_or_default(),
 		ts: r.get(1).unwrap_or_default(),
@@ -339,9 +992,179 @@ fn row_to_audit(r: &r2d2_sqlite::rusqlite::Row<'_>) -> AuditEntry {
 		target: r.get(4).unwrap_or_default(),
 		before: r.get::<_, Option<String>>(5).ok().flatten().and_then(|s| serde_json::from_str(&s).ok()),
 		after: r.get::<_, Option<String>>(6).ok().flatten().and_then(|s| serde_json::from_str(&s).ok()),
+		batch_id: r.get(7).unwrap_or_default(),
 	}
 }
 
-fn map_audit_row(r: &r2d2_sqlite::rusqlite::Row<'_>) -> Result<AuditEntry, r2d2_sqlite::rusqlite::Error> {
+fn map_frozen_row(r: &Row<'_>) -> Result<FrozenMint, r2d2_sqlite::rusqlite::Error> {
+	Ok(FrozenMint {
+		mint: r.get(0)?,
+		reason: r.get(1)?,
+		frozen_at: r.get(2)?,
+		frozen_by: r.get(3)?,
+		auto: r.get::<_, i64>(4)? != 0,
+	})
+}
+
+fn get_frozen_row(conn: &Connection, mint: &str) -> AppResult<Option<FrozenMint>> {
+	let row = conn
+		.query_row("SELECT mint, reason, frozen_at, frozen_by, auto FROM frozen_mints WHERE mint = ?", params![mint], map_frozen_row)
+		.optional()?;
+	Ok(row)
+}
+
+fn map_schedule_row(r: &Row<'_>) -> Result<ScheduledPrice, r2d2_sqlite::rusqlite::Error> {
+	Ok(ScheduledPrice {
+		id: r.get(0)?,
+		mint: r.get(1)?,
+		symbol: r.get(2)?,
+		usd_mantissa: r.get(3)?,
+		usd_scale: r.get::<_, i64>(4)? as u32,
+		decimals: r.get(5)?,
+		run_at: r.get(6)?,
+		status: r.get(7)?,
+		created_at: r.get(8)?,
+		created_by: r.get(9)?,
+		applied_at: r.get(10)?,
+		error: r.get(11)?,
+	})
+}
+
+fn get_schedule_row(conn: &Connection, id: &str) -> AppResult<Option<ScheduledPrice>> {
+	let row = conn
+		.query_row(&format!("SELECT {} FROM scheduled_prices WHERE id = ?", SCHEDULE_COLUMNS), params![id], map_schedule_row)
+		.optional()?;
+	Ok(row)
+}
+
+fn insert_peg_row(conn: &Connection, spec: &SourceSpec, actor: &str) -> AppResult<PegSource> {
+	let id = Uuid::new_v4().to_string();
+	let now = Price::now_iso();
+	conn.execute(
+		"INSERT INTO peg_sources (id, mint, spec, created_at, created_by, updated_at, updated_by) VALUES (?, ?, ?, ?, ?, ?, ?)",
+		params![id, spec.mint, serde_json::to_string(spec)?, now, actor, now, actor],
+	)?;
+	get_peg_row(conn, &id)?.ok_or(AppError::NotFound)
+}
+
+fn map_peg_row(r: &Row<'_>) -> Result<PegSource, r2d2_sqlite::rusqlite::Error> {
+	let spec: String = r.get(1)?;
+	Ok(PegSource {
+		id: r.get(0)?,
+		spec: serde_json::from_str(&spec).map_err(|e| r2d2_sqlite::rusqlite::Error::FromSqlConversionFailure(1, r2d2_sqlite::rusqlite::types::Type::Text, Box::new(e)))?,
+		created_at: r.get(2)?,
+		created_by: r.get(3)?,
+		updated_at: r.get(4)?,
+		updated_by: r.get(5)?,
+	})
+}
+
+fn get_peg_row(conn: &Connection, id: &str) -> AppResult<Option<PegSource>> {
+	let row = conn
+		.query_row(&format!("SELECT {} FROM peg_sources WHERE id = ?", PEG_COLUMNS), params![id], map_peg_row)
+		.optional()?;
+	Ok(row)
+}
+
+fn map_audit_row(r: &Row<'_>) -> Result<AuditEntry, r2d2_sqlite::rusqlite::Error> {
 	Ok(row_to_audit(r))
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+
+	/// Fresh database in a temp file; removed when dropped.
+	pub(crate) struct TempDb {
+		pub db: DbState,
+		path: PathBuf,
+	}
+
+	impl Drop for TempDb {
+		fn drop(&mut self) {
+			for suffix in ["", "-wal", "-shm"] {
+				let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+			}
+		}
+	}
+
+	pub(crate) fn temp_db() -> TempDb {
+		let path = std::env::temp_dir().join(format!("oracle-test-{}.sqlite", Uuid::new_v4()));
+		TempDb { db: DbState::open(path.clone()).unwrap(), path }
+	}
+
+	pub(crate) fn price(mint: &str, symbol: Option<&str>, mantissa: &str) -> Price {
+		Price {
+			mint: mint.into(),
+			symbol: symbol.map(str::to_string),
+			usd_mantissa: mantissa.into(),
+			usd_scale: 2,
+			conf_mantissa: None,
+			updated_at: Price::now_iso(),
+			updated_by: "test".into(),
+			decimals: Some(6),
+			version: 0,
+			age_secs: None,
+			stale: None,
+		}
+	}
+
+	#[test]
+	fn deleted_symbol_stays_gone_after_price_writes() {
+		let t = temp_db();
+		t.db.upsert_price(&price("ZERAm", Some("ZERA"), "100"), "test", false).unwrap();
+		assert_eq!(t.db.mint_for_symbol("ZERA").unwrap(), "ZERAm");
+		t.db.delete_symbol("zera", "test").unwrap();
+		assert_eq!(t.db.get_price("ZERAm").unwrap().symbol, None);
+		let current = t.db.get_price("ZERAm").unwrap();
+		let next = Price::next_from(Some(&current), "ZERAm", crate::models::UsdAmount::new(101, 2).unwrap(), "scheduler");
+		t.db.upsert_price(&next, "scheduler", false).unwrap();
+		assert!(t.db.list_symbols().unwrap().is_empty());
+		assert!(matches!(t.db.get_symbol("ZERA"), Err(AppError::NotFound)));
+	}
+
+	#[test]
+	fn symbols_are_unique_ignoring_case() {
+		let t = temp_db();
+		t.db.upsert_symbol("ZERA", "ZERAm", "test").unwrap();
+		assert!(matches!(t.db.upsert_symbol("zera", "OTHERm", "test"), Err(AppError::Conflict(_))));
+		assert_eq!(t.db.upsert_symbol("zera", "ZERAm", "test").unwrap().symbol, "ZERA");
+		t.db.upsert_symbol("ZERA", "OTHERm", "test").unwrap();
+		assert_eq!(t.db.list_symbols().unwrap().len(), 1);
+		assert_eq!(t.db.get_symbol("zera").unwrap().mint, "OTHERm");
+		assert_eq!(t.db.mint_for_symbol("zera").unwrap(), "OTHERm");
+	}
+
+	#[test]
+	fn rewriting_the_same_symbol_does_not_touch_mappings() {
+		let t = temp_db();
+		t.db.upsert_price(&price("ZERAm", Some("ZERA"), "100"), "test", false).unwrap();
+		t.db.upsert_symbol("ZERA", "OTHERm", "test").unwrap();
+		t.db.upsert_price(&price("ZERAm", Some("ZERA"), "101"), "test", false).unwrap();
+		assert_eq!(t.db.mint_for_symbol("ZERA").unwrap(), "OTHERm");
+		t.db.upsert_price(&price("ZERAm", Some("ZNEW"), "102"), "test", false).unwrap();
+		assert_eq!(t.db.mint_for_symbol("ZNEW").unwrap(), "ZERAm");
+	}
+
+	#[test]
+	fn versions_keep_climbing_across_delete() {
+		let t = temp_db();
+		let first = t.db.upsert_price(&price("M", None, "100"), "test", false).unwrap();
+		assert_eq!(first.version, 1);
+		let patched = t.db.patch_price("M", serde_json::json!({"usd_mantissa": "110"}), "test", Some(1), false).unwrap();
+		assert_eq!(patched.version, 2);
+		t.db.delete_price("M", "test", Some(2), false).unwrap();
+		let recreated = t.db.upsert_price(&price("M", None, "120"), "test", false).unwrap();
+		assert_eq!(recreated.version, 3);
+		// an ETag taken before the delete no longer matches
+		assert!(matches!(t.db.patch_price("M", serde_json::json!({"usd_mantissa": "130"}), "test", Some(1), false), Err(AppError::Conflict(_))));
+		assert!(matches!(t.db.patch_price("M", serde_json::json!({"usd_mantissa": "130"}), "test", Some(2), false), Err(AppError::Conflict(_))));
+		assert_eq!(t.db.get_price("M").unwrap().usd_mantissa, "120");
+	}
+
+	#[test]
+	fn symbol_keys_are_not_stored_as_mints() {
+		let t = temp_db();
+		assert!(matches!(t.db.upsert_price(&price("symbol:ZERA", None, "100"), "test", false), Err(AppError::BadRequest(_))));
+		assert!(t.db.list_prices().unwrap().is_empty());
+	}
+}
@@ -9,6 +9,8 @@ use thiserror::Error;
 pub enum AppError {
 	#[error("not found")] 
 	NotFound,
+	#[error("no price: {0}")] 
+	NoPrice(String),
 	#[error("unauthorized")] 
 	Unauthorized,
 	#[error("forbidden")] 
@@ -19,6 +21,14 @@ pub enum AppError {
 	BadRequest(String),
 	#[error("conflict: {0}")] 
 	Conflict(String),
+	#[error("precondition failed: {0}")] 
+	PreconditionFailed(String),
+	#[error("unprocessable: {0}")] 
+	Unprocessable(String),
+	#[error("stale price: {}", .0.join(", "))] 
+	Stale(Vec<String>),
+	#[error("circuit breaker: {mint} moved {move_bps} bps (limit {limit_bps}); mint frozen")] 
+	CircuitBreaker { mint: String, move_bps: u128, limit_bps: u32 },
 	#[error(transparent)] 
 	Sqlite(#[from] r2d2_sqlite::rusqlite::Error),
 	#[error(transparent)] 
@@ -33,11 +43,16 @@ impl AppError {
 	pub fn status(&self) -> Status {
 		match self {
 			AppError::NotFound => Status::NotFound,
+			AppError::NoPrice(_) => Status::NotFound,
 			AppError::Unauthorized => Status::Unauthorized,
 			AppError::Forbidden => Status::Forbidden,
 			AppError::TooManyRequests => Status::TooManyRequests,
 			AppError::BadRequest(_) => Status::BadRequest,
 			AppError::Conflict(_) => Status::Conflict,
+			AppError::PreconditionFailed(_) => Status::PreconditionFailed,
+			AppError::Unprocessable(_) => Status::UnprocessableEntity,
+			AppError::Stale(_) => Status::ServiceUnavailable,
+			AppError::CircuitBreaker { .. } => Status::Conflict,
 			AppError::Sqlite(_) => Status::InternalServerError,
 			AppError::Jwt(_) => Status::Unauthorized,
 			AppError::Json(_) => Status::BadRequest,
@@ -49,10 +64,11 @@ impl AppError {
 impl<'r> Responder<'r, 'static> for AppError {
 	fn respond_to(self, _req: &'r Request<'_>) -> Result<Response<'static>, Status> {
 		let status = self.status();
-		let body = json!({
+		let mut body = json!({
 			"error": self.to_string(),
 			"code": status.code,
 		});
+		if let AppError::Stale(mints) = &self { body["mints"] = json!(mints); }
 		Response::build()
 			.status(status)
 			.sized_body(None, Cursor::new(body.to_string()))
@@ -0,0 +1,51 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::auth::AuthUser;
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::models::FrozenMint;
+use crate::rate_limit::RateLimiter;
+use crate::realtime::Broadcaster;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FreezeBody {
+	#[serde(default)]
+	reason: Option<String>,
+}
+
+#[post("/prices/<mint>/freeze", data = "<body>")]
+pub fn freeze_mint(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str, body: Option<Json<FreezeBody>>) -> AppResult<Json<FrozenMint>> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let mint = db.resolve_mint(mint)?;
+	let reason = body.and_then(|b| b.into_inner().reason).unwrap_or_else(|| "frozen by admin".into());
+	let frozen = db.freeze_mint(&mint, &reason, &user.subject, false)?;
+	bc.publish(json!({"type":"mint_freeze","frozen": frozen}));
+	Ok(Json(frozen))
+}
+
+#[delete("/prices/<mint>/freeze")]
+pub fn unfreeze_mint(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = db.unfreeze_mint(&db.resolve_mint(mint)?, &user.subject)?;
+	bc.publish(json!({"type":"mint_unfreeze","frozen": removed}));
+	Ok(Status::NoContent)
+}
+
+#[get("/frozen")]
+pub fn list_frozen(db: &State<DbState>) -> AppResult<Json<Vec<FrozenMint>>> {
+	Ok(Json(db.list_frozen()?))
+}
+
+/// Publishes a `circuit_breaker` alert when a write failed because it tripped the breaker.
+/// Every price writer passes its errors through here.
+pub fn alert_on_breaker(bc: &Broadcaster, err: &AppError) {
+	if let AppError::CircuitBreaker { mint, move_bps, limit_bps } = err {
+		bc.publish(json!({"type":"circuit_breaker","mint": mint, "move_bps": move_bps.to_string(), "limit_bps": limit_bps, "error": err.to_string()}));
+	}
+}
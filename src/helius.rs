@@ -2,6 +2,7 @@ use dashmap::DashMap;
 use rocket::http::Status;
 use std::time::{Duration, Instant};
 
+use crate::db::DbState;
 use crate::errors::{AppError, AppResult};
 use crate::realtime::Broadcaster;
 
@@ -59,7 +60,8 @@ impl HeliusPriceService {
 		}
 	}
 
-	pub fn spawn_watcher(&self, bc: Broadcaster) {
+	/// Publishes `helius_price` events for `HELIUS_WATCH_MINTS`, skipping frozen mints.
+	pub fn spawn_watcher(&self, db: DbState, bc: Broadcaster) {
 		let this = self.clone();
 		let mints: Vec<String> = std::env::var("HELIUS_WATCH_MINTS")
 			.ok()
@@ -72,6 +74,7 @@ impl HeliusPriceService {
 			loop {
 				interval.tick().await;
 				for mint in &mints {
+					if db.is_frozen(mint) { continue; }
 					if let Ok((status, body)) = this.get_cached_price(mint).await {
 						if status.code == 200 {
 							if let Ok(val) = serde_json::from_str::<serde_json::Value>(&body) {
@@ -84,6 +87,17 @@ impl HeliusPriceService {
 		});
 	}
 
+	/// USD price from the TTL cache or a fresh lookup; `None` when Helius has no price for the mint.
+	pub async fn price_usd(&self, mint: &str) -> AppResult<Option<f64>> {
+		let now = Instant::now();
+		if let Some(entry) = self.cache.get(mint) {
+			if now.duration_since(entry.stored_at) < self.ttl { return Ok(Some(entry.usd)); }
+		}
+		let usd = self.fetch_price_usd(mint).await?;
+		if let Some(usd) = usd { self.cache.insert(mint.to_string(), PriceCache { usd, stored_at: now }); }
+		Ok(usd)
+	}
+
 	async fn fetch_price_usd(&self, mint: &str) -> AppResult<Option<f64>> {
 		if self.api_url.is_empty() { return Err(AppError::BadRequest("HELIUS_API_KEY or HELIUS_RPC_URL not configured".into())); }
 		let req = serde_json::json!({
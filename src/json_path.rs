@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::value::RawValue;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::UsdAmount;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+	Key(String),
+	Index(usize),
+}
+
+/// Location of a value in a JSON document: an RFC 6901 pointer (`/data/0/price`) or a dotted path
+/// with optional array indices (`data.prices[0].usd`, `[2].price`). The empty path is the document itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+	raw: String,
+	segments: Vec<Segment>,
+}
+
+impl JsonPath {
+	pub fn parse(path: &str) -> AppResult<Self> {
+		let bad = |msg: &str| AppError::BadRequest(format!("invalid path {:?}: {}", path, msg));
+		let mut segments = Vec::new();
+		if let Some(pointer) = path.strip_prefix('/') {
+			for token in pointer.split('/') {
+				segments.push(Segment::Key(unescape_token(token).ok_or_else(|| bad("`~` must be followed by 0 or 1"))?));
+			}
+		} else if !path.is_empty() {
+			for part in path.split('.') {
+				let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+				if key.is_empty() && rest.is_empty() { return Err(bad("empty segment")); }
+				if !key.is_empty() { segments.push(Segment::Key(key.to_string())); }
+				while !rest.is_empty() {
+					let close = rest.find(']').ok_or_else(|| bad("unclosed `[`"))?;
+					let index = rest[1..close].trim().parse::<usize>().map_err(|_| bad("index must be a non-negative integer"))?;
+					segments.push(Segment::Index(index));
+					rest = &rest[close + 1..];
+					if !rest.is_empty() && !rest.starts_with('[') { return Err(bad("expected `.` or `[` after `]`")); }
+				}
+			}
+		}
+		Ok(Self { raw: path.to_string(), segments })
+	}
+
+	/// Exact decimal at this path in `body`. Numbers are read from their literal text and strings are
+	/// parsed as decimals, so neither passes through f64.
+	pub fn decimal_at(&self, body: &str) -> AppResult<UsdAmount> {
+		let mut cur: &RawValue = serde_json::from_str(body)?;
+		for (depth, segment) in self.segments.iter().enumerate() {
+			let text = cur.get();
+			let next = match (text.as_bytes().first(), segment) {
+				(Some(b'{'), Segment::Key(key)) => serde_json::from_str::<HashMap<String, &RawValue>>(text)?.remove(key),
+				(Some(b'['), Segment::Index(i)) => serde_json::from_str::<Vec<&RawValue>>(text)?.get(*i).copied(),
+				(Some(b'['), Segment::Key(key)) => match key.parse::<usize>() {
+					Ok(i) => serde_json::from_str::<Vec<&RawValue>>(text)?.get(i).copied(),
+					Err(_) => None,
+				},
+				_ => None,
+			};
+			cur = next.ok_or_else(|| AppError::NoPrice(format!("nothing at {:?} (missing {})", self.raw, Prefix(&self.segments[..=depth]))))?;
+		}
+		let text = cur.get();
+		let decimal = match text.as_bytes().first() {
+			Some(b'"') => serde_json::from_str::<String>(text)?,
+			Some(b'-' | b'0'..=b'9') => text.to_string(),
+			_ => return Err(AppError::NoPrice(format!("value at {:?} is not a number: {}", self.raw, text))),
+		};
+		UsdAmount::from_decimal_str(&decimal)
+	}
+}
+
+/// Decodes `~1` to `/` and `~0` to `~`; `None` for any other use of `~`.
+fn unescape_token(token: &str) -> Option<String> {
+	let mut out = String::with_capacity(token.len());
+	let mut chars = token.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'~' => match chars.next()? {
+				'0' => out.push('~'),
+				'1' => out.push('/'),
+				_ => return None,
+			},
+			c => out.push(c),
+		}
+	}
+	Some(out)
+}
+
+/// Renders the segments walked so far in dotted form, for error messages.
+struct Prefix<'a>(&'a [Segment]);
+
+impl fmt::Display for Prefix<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (i, segment) in self.0.iter().enumerate() {
+			match segment {
+				Segment::Key(key) if i == 0 => write!(f, "{}", key)?,
+				Segment::Key(key) => write!(f, ".{}", key)?,
+				Segment::Index(index) => write!(f, "[{}]", index)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read(path: &str, body: &str) -> AppResult<String> {
+		JsonPath::parse(path)?.decimal_at(body).map(|d| d.to_string())
+	}
+
+	#[test]
+	fn pointer_escapes() {
+		let body = r#"{"a/b":{"c~d":"1.5"},"~1":2}"#;
+		assert_eq!(read("/a~1b/c~0d", body).unwrap(), "1.5");
+		assert_eq!(read("/~01", body).unwrap(), "2");
+		assert!(matches!(JsonPath::parse("/a~2b"), Err(AppError::BadRequest(_))));
+		assert!(matches!(JsonPath::parse("/a~"), Err(AppError::BadRequest(_))));
+	}
+
+	#[test]
+	fn indices() {
+		let body = r#"{"data":{"prices":[{"usd":"0.1"},{"usd":"0.25"}]},"rows":[[1,2],[3,4]]}"#;
+		assert_eq!(read("data.prices[1].usd", body).unwrap(), "0.25");
+		assert_eq!(read("/data/prices/0/usd", body).unwrap(), "0.1");
+		assert_eq!(read("rows[1][0]", body).unwrap(), "3");
+		assert_eq!(read("[1].price", r#"[{"price":1},{"price":"7.5"}]"#).unwrap(), "7.5");
+		assert_eq!(read("", "42").unwrap(), "42");
+		for bad in ["data.prices[x]", "data.prices[0", "data..usd", "rows[0]x", "rows[-1]"] {
+			assert!(matches!(JsonPath::parse(bad), Err(AppError::BadRequest(_))), "{:?}", bad);
+		}
+	}
+
+	#[test]
+	fn exact_leaves() {
+		assert_eq!(read("p", r#"{"p":"1.2345"}"#).unwrap(), "1.2345");
+		assert_eq!(read("p", r#"{"p":0.1000000000000000055511151231257827}"#).unwrap(), "0.1000000000000000055511151231257827");
+		assert_eq!(read("p", r#"{"p":1.5e-3}"#).unwrap(), "0.0015");
+		assert!(matches!(read("p", r#"{"p":true}"#), Err(AppError::NoPrice(_))));
+		assert!(matches!(read("p", r#"{"p":null}"#), Err(AppError::NoPrice(_))));
+		assert!(matches!(read("p", r#"{"p":"n/a"}"#), Err(AppError::BadRequest(_))));
+	}
+
+	#[test]
+	fn missing_segments_name_the_prefix() {
+		let body = r#"{"data":{"prices":[{"usd":1}]}}"#;
+		let err = read("data.quotes.usd", body).unwrap_err().to_string();
+		assert!(err.contains("missing data.quotes"), "{}", err);
+		let err = read("data.prices[3].usd", body).unwrap_err().to_string();
+		assert!(err.contains("missing data.prices[3]"), "{}", err);
+		// a key into an array or an index into an object is missing, not skipped
+		assert!(read("data.prices.usd", body).is_err());
+		assert!(read("data[0]", body).is_err());
+	}
+}
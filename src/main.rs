@@ -3,28 +3,45 @@
 #[macro_use]
 extern crate rocket;
 
+mod aggregate;
 mod auth;
+mod candles;
 mod db;
 mod errors;
+mod freeze;
+mod json_path;
 mod models;
+mod pegger;
+mod quote;
 mod rate_limit;
 mod routes;
+mod scenarios;
+mod scheduler;
+mod sources;
 mod realtime;
+mod replay;
+mod pyth;
 mod qn_proxy;
 mod helius;
 
+use std::sync::Arc;
+
 use dotenvy::dotenv;
 use rocket::fairing::AdHoc;
 use rocket_cors::{AllowedHeaders, AllowedMethods, AllowedOrigins, CorsOptions};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::db::DbState;
-use crate::models::Price;
+use crate::models::{Price, UsdAmount};
+use crate::pegger::Pegger;
 use crate::rate_limit::RateLimiter;
 use crate::qn_proxy::QuicknodeProxy;
 use crate::realtime::Broadcaster;
+use crate::replay::ReplayEngine;
 use crate::routes::mount_routes;
+use crate::scenarios::ScenarioEngine;
 use crate::helius::HeliusPriceService;
+use crate::sources::SourceDeps;
 
 #[launch]
 fn rocket() -> _ {
@@ -39,8 +56,17 @@ fn rocket() -> _ {
 
 	let db = DbState::initialize().expect("failed to init database");
 	seed_fixtures(&db);
-	spawn_pegger_if_configured(db.clone());
+	seed_pegs(&db);
 	let broadcaster = Broadcaster::new();
+	let proxy = QuicknodeProxy::from_env();
+	let helius = HeliusPriceService::from_env();
+	let deps = SourceDeps {
+		client: reqwest::Client::new(),
+		helius: helius.clone(),
+		proxy: Arc::new(proxy.clone_shallow()),
+		solana_rpc_url: std::env::var("SOLANA_RPC_URL").ok().filter(|v| !v.trim().is_empty()),
+	};
+	let pegger = Pegger::new(deps, db.clone(), broadcaster.clone());
 	let limiter = RateLimiter::new_per_minute(std::env::var("WRITE_RATE_LIMIT_PER_MINUTE").ok().and_then(|v| v.parse().ok()).unwrap_or(60));
 
 	let cors = build_cors();
@@ -48,9 +74,12 @@ fn rocket() -> _ {
 	let rocket = rocket::build()
 		.manage(db.clone())
 		.manage(broadcaster)
-		.manage(QuicknodeProxy::from_env())
-		.manage(HeliusPriceService::from_env())
+		.manage(proxy)
+		.manage(helius)
+		.manage(pegger)
 		.manage(limiter)
+		.manage(ScenarioEngine::new())
+		.manage(ReplayEngine::new())
 		.attach(cors)
 		.mount("/api/v1", mount_routes())
 		.attach(AdHoc::on_liftoff("hotset refresher", |rocket| Box::pin(async move {
@@ -58,13 +87,19 @@ fn rocket() -> _ {
 			let proxy = rocket.state::<QuicknodeProxy>();
 			let bc = rocket.state::<Broadcaster>().cloned();
 			let helius = rocket.state::<HeliusPriceService>().cloned();
-			if let (Some(db), Some(proxy)) = (db, proxy) {
+			if let (Some(db), Some(bc)) = (db.clone(), bc.clone()) {
+				scheduler::spawn_scheduler(db, bc);
+			}
+			if let (Some(db), Some(proxy)) = (db.clone(), proxy) {
 				if std::env::var("QNODE_L2_ENABLED").ok().map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(true) {
 					proxy.spawn_hotset_refresher(db);
 				}
 			}
-			if let (Some(helius), Some(bc)) = (helius, bc) {
-				helius.spawn_watcher(bc);
+			if let Some(pegger) = rocket.state::<Pegger>() {
+				if let Err(e) = pegger.reload() { tracing::warn!("pegger: failed to load peg sources: {}", e); }
+			}
+			if let (Some(helius), Some(db), Some(bc)) = (helius, db, bc) {
+				helius.spawn_watcher(db, bc);
 			}
 		})));
 
@@ -99,70 +134,53 @@ fn seed_fixtures(db: &DbState) {
 	let usdc_mint = std::env::var("USDC_DEVNET_MINT").ok();
 	let zera_mint = std::env::var("ZERA_DEVNET_MINT").ok();
 	if let Some(mint) = usdc_mint {
-		let price = Price {
+		let mut price = Price {
 			mint: mint.clone(),
 			symbol: Some("USDC".into()),
-			usd_mantissa: "100".into(),
-			usd_scale: 2,
+			usd_mantissa: String::new(),
+			usd_scale: 0,
+			conf_mantissa: None,
 			updated_at: Price::now_iso(),
 			updated_by: "seed".into(),
 			decimals: Some(6),
+			version: 0,
+			age_secs: None,
+			stale: None,
 		};
-		let _ = db.upsert_price(&price, "seed");
+		price.set_usd_amount(UsdAmount::from_decimal_str("1.00").expect("valid seed price"));
+		seed_price(db, &price);
 	}
 	if let Some(mint) = zera_mint {
-		let price = Price {
+		let mut price = Price {
 			mint: mint.clone(),
 			symbol: Some("ZERA".into()),
-			usd_mantissa: "10".into(),
-			usd_scale: 2,
+			usd_mantissa: String::new(),
+			usd_scale: 0,
+			conf_mantissa: None,
 			updated_at: Price::now_iso(),
 			updated_by: "seed".into(),
 			decimals: Some(6),
+			version: 0,
+			age_secs: None,
+			stale: None,
 		};
-		let _ = db.upsert_price(&price, "seed");
+		price.set_usd_amount(UsdAmount::from_decimal_str("0.10").expect("valid seed price"));
+		seed_price(db, &price);
 	}
 }
 
-fn spawn_pegger_if_configured(db: DbState) {
-	let sources = std::env::var("PEG_SOURCES").ok();
-	if sources.is_none() { return; }
-	let sources = sources.unwrap();
-	if sources.trim().is_empty() { return; }
-	tokio::spawn(async move {
-		let client = reqwest::Client::new();
-		let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
-		let parsed: Vec<_> = sources.split(';').filter(|s| !s.trim().is_empty()).collect();
-		loop {
-			interval.tick().await;
-			for src in &parsed {
-				// Format: mint|url|jsonPointer|scale
-				let parts: Vec<&str> = src.split('|').collect();
-				if parts.len() < 4 { continue; }
-				let mint = parts[0].to_string();
-				let url = parts[1];
-				let pointer = parts[2];
-				let scale: u32 = parts[3].parse().unwrap_or(2);
-				if let Ok(resp) = client.get(url).send().await {
-					if let Ok(val) = resp.json::<serde_json::Value>().await {
-						let mut cur = &val;
-						for key in pointer.split('.') { if let Some(v) = cur.get(key) { cur = v; } }
-						if let Some(price_num) = cur.as_f64() {
-							let mantissa = ((price_num * 10f64.powi(scale as i32)).round() as i128).to_string();
-							let price = Price {
-								mint: mint.clone(),
-								symbol: None,
-								usd_mantissa: mantissa,
-								usd_scale: scale,
-								updated_at: Price::now_iso(),
-								updated_by: "pegger".into(),
-								decimals: None,
-							};
-							let _ = db.upsert_price(&price, "pegger");
-						}
-					}
-				}
-			}
-		}
-	});
+/// `PRICE_SOURCES`/`PEG_SOURCES` only seed `peg_sources` on a fresh database; after that `/pegs` owns them.
+fn seed_pegs(db: &DbState) {
+	let specs = sources::specs_from_env();
+	match db.seed_pegs(&specs) {
+		Ok(0) => {}
+		Ok(n) => tracing::info!("seeded {} peg sources from the environment", n),
+		Err(e) => tracing::warn!("failed to seed peg sources: {}", e),
+	}
+}
+
+/// Fixtures are re-applied on every start, so they skip frozen mints and bypass the circuit breaker.
+fn seed_price(db: &DbState, price: &Price) {
+	if db.is_frozen(&price.mint) { return; }
+	let _ = db.upsert_price(price, "seed", true);
 }
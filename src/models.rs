@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::errors::{AppError, AppResult};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Price {
 	pub mint: String,
@@ -9,16 +12,356 @@ pub struct Price {
 	pub symbol: Option<String>,
 	pub usd_mantissa: String,
 	pub usd_scale: u32,
+	/// Confidence interval (±) at `usd_scale`, Pyth style
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub conf_mantissa: Option<String>,
 	pub updated_at: String,
 	pub updated_by: String,
 	#[serde(skip_serializing_if = "Option::is_none")] 
 	pub decimals: Option<u8>,
+	/// Bumped on every write; exposed as the `ETag` of `GET /prices/<mint>`
+	#[serde(default)]
+	pub version: u64,
+	/// Seconds since `updated_at`; only set on live reads
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub age_secs: Option<u64>,
+	/// Whether the price is older than its configured max age; only set on live reads
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub stale: Option<bool>,
 }
 
 impl Price {
 	pub fn now_iso() -> String {
 		OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_else(|_| "".into())
 	}
+
+	/// A fresh write of `amount` for `mint` by a background writer, keeping the symbol and decimals of `current`.
+	pub fn next_from(current: Option<&Price>, mint: &str, amount: UsdAmount, updated_by: &str) -> Price {
+		let mut price = Price {
+			mint: mint.to_string(),
+			symbol: current.and_then(|p| p.symbol.clone()),
+			usd_mantissa: String::new(),
+			usd_scale: 0,
+			conf_mantissa: None,
+			updated_at: Price::now_iso(),
+			updated_by: updated_by.to_string(),
+			decimals: current.and_then(|p| p.decimals),
+			version: 0,
+			age_secs: None,
+			stale: None,
+		};
+		price.set_usd_amount(amount);
+		price
+	}
+
+	/// Validated exact value of `usd_mantissa`/`usd_scale`.
+	pub fn usd_amount(&self) -> AppResult<UsdAmount> {
+		UsdAmount::parse(&self.usd_mantissa, self.usd_scale)
+	}
+
+	pub fn set_usd_amount(&mut self, amount: UsdAmount) {
+		self.usd_mantissa = amount.mantissa().to_string();
+		self.usd_scale = amount.scale();
+	}
+
+	/// Validated exact value of `conf_mantissa`, at `usd_scale`.
+	pub fn conf_amount(&self) -> AppResult<Option<UsdAmount>> {
+		match &self.conf_mantissa {
+			Some(conf) => UsdAmount::parse(conf, self.usd_scale)
+				.map(Some)
+				.map_err(|_| AppError::BadRequest(format!("conf_mantissa must be a non-negative integer string, got {:?}", conf))),
+			None => Ok(None),
+		}
+	}
+
+	/// `updated_at` as unix milliseconds; falls back to now for rows with an unparsable timestamp.
+	pub fn updated_at_ms(&self) -> i64 {
+		parse_rfc3339_ms(&self.updated_at).unwrap_or_else(now_ms)
+	}
+
+	/// Fills `age_secs`/`stale` as of `now_ms`. A price with an unparsable `updated_at` counts as stale
+	/// whenever a max age applies.
+	pub fn set_freshness(&mut self, max_age_secs: Option<u64>, now_ms: i64) {
+		let age = parse_rfc3339_ms(&self.updated_at).map(|ts| (now_ms - ts).max(0) as u64 / 1000);
+		self.age_secs = age;
+		self.stale = Some(match (max_age_secs, age) {
+			(Some(max), Some(age)) => age > max,
+			(Some(_), None) => true,
+			(None, _) => false,
+		});
+	}
+}
+
+pub fn now_ms() -> i64 {
+	(OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// Unix milliseconds as RFC3339; empty for out-of-range values.
+pub fn format_ms(ms: i64) -> String {
+	OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000)
+		.ok()
+		.and_then(|t| t.format(&time::format_description::well_known::Rfc3339).ok())
+		.unwrap_or_default()
+}
+
+pub fn parse_rfc3339_ms(s: &str) -> Option<i64> {
+	OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)
+		.ok()
+		.map(|t| (t.unix_timestamp_nanos() / 1_000_000) as i64)
+}
+
+/// Largest scale whose power of ten still fits in a `u128`.
+pub const MAX_SCALE: u32 = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+	#[default]
+	Floor,
+	Ceil,
+	#[serde(alias = "half-even")]
+	HalfEven,
+}
+
+impl Rounding {
+	pub fn parse(s: &str) -> AppResult<Self> {
+		match s.to_ascii_lowercase().as_str() {
+			"floor" => Ok(Rounding::Floor),
+			"ceil" => Ok(Rounding::Ceil),
+			"half_even" | "half-even" => Ok(Rounding::HalfEven),
+			_ => Err(AppError::BadRequest("rounding must be one of floor, ceil, half_even".into())),
+		}
+	}
+}
+
+/// `num / den` rounded with `rounding`; `den` must be non-zero.
+pub fn div_round(num: u128, den: u128, rounding: Rounding) -> u128 {
+	let q = num / den;
+	let r = num % den;
+	if r == 0 { return q; }
+	match rounding {
+		Rounding::Floor => q,
+		Rounding::Ceil => q + 1,
+		Rounding::HalfEven => {
+			// compare r against den - r to avoid overflowing on 2 * r
+			match r.cmp(&(den - r)) {
+				std::cmp::Ordering::Less => q,
+				std::cmp::Ordering::Greater => q + 1,
+				std::cmp::Ordering::Equal => if q.is_multiple_of(2) { q } else { q + 1 },
+			}
+		}
+	}
+}
+
+pub fn pow10(exp: u32) -> Option<u128> {
+	10u128.checked_pow(exp)
+}
+
+/// `mantissa / 10^digits`, rounded.
+fn drop_digits(mantissa: u128, digits: u64, rounding: Rounding) -> u128 {
+	match u32::try_from(digits).ok().and_then(pow10) {
+		Some(f) => div_round(mantissa, f, rounding),
+		// 10^digits exceeds every u128, so the quotient is below one half
+		None => if rounding == Rounding::Ceil && mantissa > 0 { 1 } else { 0 },
+	}
+}
+
+/// Exact non-negative USD amount, `mantissa / 10^scale`, matching the `usd_mantissa`/`usd_scale` price model.
+/// Equality and ordering compare the numeric value, so `10/1 == 100/2`.
+#[derive(Debug, Clone, Copy)]
+pub struct UsdAmount {
+	mantissa: u128,
+	scale: u32,
+}
+
+impl UsdAmount {
+	pub fn new(mantissa: u128, scale: u32) -> AppResult<Self> {
+		if scale > MAX_SCALE { return Err(AppError::BadRequest(format!("usd_scale must be <= {}", MAX_SCALE))); }
+		Ok(Self { mantissa, scale })
+	}
+
+	pub fn zero() -> Self {
+		Self { mantissa: 0, scale: 0 }
+	}
+
+	/// Parses a stored `usd_mantissa` string (unsigned base-10 integer) at `scale`.
+	pub fn parse(mantissa: &str, scale: u32) -> AppResult<Self> {
+		let m = mantissa.trim();
+		if m.is_empty() || !m.bytes().all(|b| b.is_ascii_digit()) {
+			return Err(AppError::BadRequest(format!("usd_mantissa must be a non-negative integer string, got {:?}", mantissa)));
+		}
+		let mantissa = m.parse::<u128>().map_err(|_| AppError::BadRequest("usd_mantissa out of range".into()))?;
+		Self::new(mantissa, scale)
+	}
+
+	/// Parses a human decimal such as `"12.345"`, `"0.5"` or `"1e-7"`; the scale is the number of fractional digits.
+	pub fn from_decimal_str(s: &str) -> AppResult<Self> {
+		let bad = || AppError::BadRequest(format!("invalid decimal {:?}", s));
+		let t = s.trim();
+		let t = t.strip_prefix('+').unwrap_or(t);
+		let (num, exp) = match t.find(['e', 'E']) {
+			Some(i) => (&t[..i], t[i + 1..].parse::<i32>().map_err(|_| bad())?),
+			None => (t, 0),
+		};
+		let (int_part, frac_part) = match num.split_once('.') {
+			Some((i, f)) => (i, f),
+			None => (num, ""),
+		};
+		if int_part.is_empty() && frac_part.is_empty() { return Err(bad()); }
+		if !int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_digit()) { return Err(bad()); }
+		let digits = format!("{}{}", int_part, frac_part);
+		let digits = digits.trim_start_matches('0');
+		let mut mantissa: u128 = if digits.is_empty() { 0 } else { digits.parse().map_err(|_| AppError::BadRequest("decimal out of range".into()))? };
+		let mut scale = frac_part.len() as i64 - exp as i64;
+		if scale < 0 {
+			mantissa = pow10((-scale) as u32)
+				.and_then(|f| mantissa.checked_mul(f))
+				.ok_or_else(|| AppError::BadRequest("decimal out of range".into()))?;
+			scale = 0;
+		}
+		if scale > MAX_SCALE as i64 {
+			// drop digits beyond what a u128 scale can represent
+			let mantissa = drop_digits(mantissa, (scale - MAX_SCALE as i64) as u64, Rounding::HalfEven);
+			return Self::new(mantissa, MAX_SCALE);
+		}
+		Self::new(mantissa, scale as u32)
+	}
+
+	pub fn mantissa(&self) -> u128 {
+		self.mantissa
+	}
+
+	pub fn scale(&self) -> u32 {
+		self.scale
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.mantissa == 0
+	}
+
+	/// Same value at `scale`; dropping digits rounds with `rounding`, adding digits fails only on overflow.
+	pub fn rescale(&self, scale: u32, rounding: Rounding) -> AppResult<Self> {
+		if scale > MAX_SCALE { return Err(AppError::BadRequest(format!("scale must be <= {}", MAX_SCALE))); }
+		if scale >= self.scale {
+			let mantissa = pow10(scale - self.scale)
+				.and_then(|f| self.mantissa.checked_mul(f))
+				.ok_or_else(|| AppError::BadRequest("amount overflows at requested scale".into()))?;
+			Ok(Self { mantissa, scale })
+		} else {
+			Ok(Self { mantissa: drop_digits(self.mantissa, (self.scale - scale) as u64, rounding), scale })
+		}
+	}
+
+	/// Smallest-scale representation of the same value (trailing zeros removed).
+	pub fn normalize(&self) -> Self {
+		let mut out = *self;
+		while out.scale > 0 && out.mantissa.is_multiple_of(10) {
+			out.mantissa /= 10;
+			out.scale -= 1;
+		}
+		if out.mantissa == 0 { out.scale = 0; }
+		out
+	}
+
+	/// Both operands brought to the larger of the two scales.
+	fn aligned(&self, other: &Self) -> Option<(u128, u128, u32)> {
+		let scale = self.scale.max(other.scale);
+		let a = self.mantissa.checked_mul(pow10(scale - self.scale)?)?;
+		let b = other.mantissa.checked_mul(pow10(scale - other.scale)?)?;
+		Some((a, b, scale))
+	}
+
+	/// Relative move from `self` to `new` in basis points, rounded up. `None` when `self` is zero or on overflow.
+	pub fn change_bps(&self, new: &Self) -> Option<u128> {
+		let (a, b, _) = self.aligned(new)?;
+		if a == 0 { return None; }
+		Some(div_round(a.abs_diff(b).checked_mul(10_000)?, a, Rounding::Ceil))
+	}
+
+	pub fn checked_add(&self, other: &Self) -> Option<Self> {
+		let (a, b, scale) = self.aligned(other)?;
+		Some(Self { mantissa: a.checked_add(b)?, scale })
+	}
+
+	pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+		let scale = self.scale.checked_add(other.scale).filter(|s| *s <= MAX_SCALE)?;
+		Some(Self { mantissa: self.mantissa.checked_mul(other.mantissa)?, scale })
+	}
+
+	/// `self / other` expressed at `scale`, rounded with `rounding`. `None` on division by zero or overflow.
+	pub fn checked_div(&self, other: &Self, scale: u32, rounding: Rounding) -> Option<Self> {
+		if other.mantissa == 0 || scale > MAX_SCALE { return None; }
+		// value = (a.m / 10^a.s) / (b.m / 10^b.s); mantissa at `scale` = a.m * 10^(b.s + scale - a.s) / b.m
+		let exp = other.scale as i64 + scale as i64 - self.scale as i64;
+		let (num, den) = if exp >= 0 {
+			(self.mantissa.checked_mul(pow10(exp as u32)?)?, other.mantissa)
+		} else {
+			(self.mantissa, other.mantissa.checked_mul(pow10((-exp) as u32)?)?)
+		};
+		Some(Self { mantissa: div_round(num, den, rounding), scale })
+	}
+
+	/// Human decimal string, e.g. `1234/2` -> `"12.34"`.
+	pub fn to_decimal_string(self) -> String {
+		let digits = self.mantissa.to_string();
+		if self.scale == 0 { return digits; }
+		let scale = self.scale as usize;
+		let padded = if digits.len() <= scale { format!("{}{}", "0".repeat(scale - digits.len() + 1), digits) } else { digits };
+		let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+		format!("{}.{}", int_part, frac_part)
+	}
+}
+
+impl std::fmt::Display for UsdAmount {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.to_decimal_string())
+	}
+}
+
+impl PartialEq for UsdAmount {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == std::cmp::Ordering::Equal
+	}
+}
+
+impl Eq for UsdAmount {}
+
+impl PartialOrd for UsdAmount {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for UsdAmount {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		match self.aligned(other) {
+			Some((a, b, _)) => a.cmp(&b),
+			// only the side being upscaled can overflow, and an overflowing value is the larger one
+			None if self.scale < other.scale => std::cmp::Ordering::Greater,
+			None => std::cmp::Ordering::Less,
+		}
+	}
+}
+
+impl Serialize for UsdAmount {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeStruct;
+		let mut st = serializer.serialize_struct("UsdAmount", 2)?;
+		st.serialize_field("usd_mantissa", &self.mantissa.to_string())?;
+		st.serialize_field("usd_scale", &self.scale)?;
+		st.end()
+	}
+}
+
+/// A mint whose price only changes through forced writes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrozenMint {
+	pub mint: String,
+	pub reason: String,
+	pub frozen_at: String,
+	pub frozen_by: String,
+	/// Set when the circuit breaker froze the mint rather than an admin
+	pub auto: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +377,81 @@ pub struct Config {
 	pub fee_bps_default: u16,
 	pub zera_mint: String,
 	pub supported_mints: Vec<String>,
+	/// Global max price age in seconds; `None` means prices never go stale
+	#[serde(default)]
+	pub max_age_secs: Option<u64>,
+	/// Per-mint max age in seconds, overriding `max_age_secs`
+	#[serde(default)]
+	pub max_age_overrides: BTreeMap<String, u64>,
+	/// Largest move in basis points a single unforced write may make before the mint is frozen; `None` disables the breaker
+	#[serde(default)]
+	pub circuit_breaker_bps: Option<u32>,
+	/// How the pegger combines readings when a mint has several sources
+	#[serde(default)]
+	pub aggregation: AggregationPolicy,
+	/// Per-mint aggregation policy, overriding `aggregation`
+	#[serde(default)]
+	pub aggregation_overrides: BTreeMap<String, AggregationPolicy>,
+}
+
+impl Config {
+	pub fn max_age_for(&self, mint: &str) -> Option<u64> {
+		self.max_age_overrides.get(mint).copied().or(self.max_age_secs)
+	}
+
+	pub fn aggregation_for(&self, mint: &str) -> AggregationPolicy {
+		self.aggregation_overrides.get(mint).cloned().unwrap_or_else(|| self.aggregation.clone())
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationMethod {
+	#[default]
+	Median,
+	/// Mean weighted by each source's `weight`
+	WeightedMean,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregationPolicy {
+	#[serde(default)]
+	pub method: AggregationMethod,
+	/// Readings further than this from the median of fresh readings are dropped; `None` keeps them all
+	#[serde(default)]
+	pub max_deviation_bps: Option<u32>,
+	/// Fewest fresh, in-band readings needed before a price is written
+	#[serde(default = "default_min_sources")]
+	pub min_sources: u32,
+	/// Readings older than this are left out
+	#[serde(default = "default_max_reading_age_secs")]
+	pub max_reading_age_secs: u64,
+}
+
+fn default_min_sources() -> u32 { 1 }
+fn default_max_reading_age_secs() -> u64 { 60 }
+
+impl Default for AggregationPolicy {
+	fn default() -> Self {
+		Self { method: AggregationMethod::Median, max_deviation_bps: None, min_sources: default_min_sources(), max_reading_age_secs: default_max_reading_age_secs() }
+	}
+}
+
+/// One source's reading as considered by an aggregated pegger write.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceReading {
+	/// Source label, e.g. `helius` or `http_json:https://...`
+	pub source: String,
+	pub kind: String,
+	pub usd_mantissa: String,
+	pub usd_scale: u32,
+	pub weight: u32,
+	pub read_at: String,
+	/// `used`, `outlier` or `stale`
+	pub status: String,
+	/// Distance from the median of fresh readings
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub deviation_bps: Option<u64>,
 }
 
 impl Default for Config {
@@ -44,10 +462,39 @@ impl Default for Config {
 			fee_bps_default: std::env::var("DEFAULT_FEE_BPS").ok().and_then(|v| v.parse().ok()).unwrap_or(100),
 			zera_mint: std::env::var("ZERA_MINT").unwrap_or_default(),
 			supported_mints: std::env::var("SUPPORTED_MINTS").map(|s| s.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect()).unwrap_or_default(),
+			max_age_secs: None,
+			max_age_overrides: BTreeMap::new(),
+			circuit_breaker_bps: None,
+			aggregation: AggregationPolicy::default(),
+			aggregation_overrides: BTreeMap::new(),
 		}
 	}
 }
 
+/// A price write queued for a future time by `POST /schedules`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledPrice {
+	pub id: String,
+	pub mint: String,
+	/// Kept from the current price when unset
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub symbol: Option<String>,
+	pub usd_mantissa: String,
+	pub usd_scale: u32,
+	/// Kept from the current price when unset
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub decimals: Option<u8>,
+	pub run_at: String,
+	/// `pending`, `applied` or `failed`
+	pub status: String,
+	pub created_at: String,
+	pub created_by: String,
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub applied_at: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEntry {
 	pub id: String,
@@ -59,6 +506,9 @@ pub struct AuditEntry {
 	pub before: Option<serde_json::Value>,
 	#[serde(skip_serializing_if = "Option::is_none")] 
 	pub after: Option<serde_json::Value>,
+	/// Shared by every entry written by one `POST /prices/batch` call
+	#[serde(default, skip_serializing_if = "Option::is_none")] 
+	pub batch_id: Option<String>,
 }
 
 impl AuditEntry {
@@ -72,6 +522,7 @@ impl AuditEntry {
 			target: target.to_string(),
 			before,
 			after,
+			batch_id: None,
 		}
 	}
 }
@@ -81,4 +532,134 @@ pub struct PaginatedAuditResponse {
 	pub entries: Vec<AuditEntry>,
 	#[serde(skip_serializing_if = "Option::is_none")] 
 	pub next_cursor: Option<String>,
-} 
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaginatedPriceHistoryResponse {
+	pub entries: Vec<Price>,
+	#[serde(skip_serializing_if = "Option::is_none")] 
+	pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceSourcesResponse {
+	pub mint: String,
+	/// Price version the readings produced; `None` when the pegger has not written this mint
+	pub price_version: Option<u64>,
+	/// Policy currently configured for the mint
+	pub policy: AggregationPolicy,
+	pub readings: Vec<SourceReading>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceQueryResponse {
+	pub prices: BTreeMap<String, Price>,
+	/// Requested mints (or `symbol:<SYMBOL>` keys) that have no price
+	pub missing: Vec<String>,
+	/// Requested mints that exist but were not updated since `updated_since`
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub not_modified: Vec<String>,
+}
+
+/// `GET /prices` keeps returning a plain list when no filter is given.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum PriceListResponse {
+	All(Vec<Price>),
+	Query(PriceQueryResponse),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricesAtResponse {
+	pub ts: String,
+	pub prices: BTreeMap<String, Price>,
+	pub missing: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parts(s: &str) -> (u128, u32) {
+		let a = UsdAmount::from_decimal_str(s).unwrap();
+		(a.mantissa(), a.scale())
+	}
+
+	fn amount(mantissa: u128, scale: u32) -> UsdAmount {
+		UsdAmount::new(mantissa, scale).unwrap()
+	}
+
+	#[test]
+	fn parses_decimals_and_exponents() {
+		assert_eq!(parts("12.345"), (12345, 3));
+		assert_eq!(parts("0.5"), (5, 1));
+		assert_eq!(parts("+2.5"), (25, 1));
+		assert_eq!(parts("1e-7"), (1, 7));
+		assert_eq!(parts("1.5e3"), (1500, 0));
+		assert_eq!(parts("12.345E1"), (12345, 2));
+		assert_eq!(parts(".5"), (5, 1));
+		assert_eq!(parts("007"), (7, 0));
+	}
+
+	#[test]
+	fn rejects_malformed_decimals() {
+		for s in ["-1", ".", "", "e5", "1e", "1.2.3", "abc", "1,5"] {
+			assert!(UsdAmount::from_decimal_str(s).is_err(), "{:?}", s);
+		}
+	}
+
+	#[test]
+	fn decimal_range_limits() {
+		assert_eq!(parts(&u128::MAX.to_string()), (u128::MAX, 0));
+		assert!(UsdAmount::from_decimal_str("340282366920938463463374607431768211456").is_err());
+		assert!(UsdAmount::from_decimal_str("1e39").is_err());
+		assert!(UsdAmount::from_decimal_str("4e38").is_err());
+		// digits past scale 38 are rounded half-even
+		assert_eq!(parts("6e-39"), (1, 38));
+		assert_eq!(parts("5e-39"), (0, 38));
+		assert_eq!(parts("15e-39"), (2, 38));
+		assert_eq!(parts("1e-400"), (0, 38));
+		assert!(UsdAmount::new(1, 39).is_err());
+	}
+
+	#[test]
+	fn rescale_rounds() {
+		let x = amount(125, 2);
+		assert_eq!(x.rescale(1, Rounding::Floor).unwrap().mantissa(), 12);
+		assert_eq!(x.rescale(1, Rounding::Ceil).unwrap().mantissa(), 13);
+		assert_eq!(x.rescale(1, Rounding::HalfEven).unwrap().mantissa(), 12);
+		assert_eq!(amount(135, 2).rescale(1, Rounding::HalfEven).unwrap().mantissa(), 14);
+		assert_eq!(amount(1251, 3).rescale(1, Rounding::HalfEven).unwrap().mantissa(), 13);
+		assert_eq!(amount(120, 2).rescale(1, Rounding::Ceil).unwrap().mantissa(), 12);
+		assert_eq!(amount(15, 1).rescale(3, Rounding::Floor).unwrap().mantissa(), 1500);
+		assert!(amount(u128::MAX, 0).rescale(1, Rounding::Floor).is_err());
+		assert!(x.rescale(MAX_SCALE + 1, Rounding::Floor).is_err());
+	}
+
+	#[test]
+	fn div_round_ties_to_even() {
+		assert_eq!(div_round(5, 2, Rounding::HalfEven), 2);
+		assert_eq!(div_round(7, 2, Rounding::HalfEven), 4);
+		assert_eq!(div_round(25, 10, Rounding::HalfEven), 2);
+		assert_eq!(div_round(35, 10, Rounding::HalfEven), 4);
+		assert_eq!(div_round(1, 3, Rounding::HalfEven), 0);
+		assert_eq!(div_round(2, 3, Rounding::HalfEven), 1);
+		assert_eq!(div_round(7, 2, Rounding::Floor), 3);
+		assert_eq!(div_round(7, 2, Rounding::Ceil), 4);
+		assert_eq!(div_round(8, 2, Rounding::Ceil), 4);
+		assert_eq!(div_round(u128::MAX, u128::MAX - 1, Rounding::HalfEven), 1);
+	}
+
+	#[test]
+	fn compares_across_scales() {
+		assert_eq!(amount(10, 1), amount(100, 2));
+		assert!(amount(15, 1) < amount(151, 2));
+		assert!(amount(2, 0) > amount(199, 2));
+		assert_eq!(amount(0, 0), amount(0, 38));
+		// aligning would overflow; the side that cannot be upscaled is the larger one
+		assert!(amount(u128::MAX, 0) > amount(1, 38));
+		assert!(amount(1, 38) < amount(u128::MAX, 0));
+		assert_eq!(amount(1234, 2).to_decimal_string(), "12.34");
+		assert_eq!(amount(5, 3).to_decimal_string(), "0.005");
+	}
+}
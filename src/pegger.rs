@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use serde_json::json;
+use tokio::task::JoinHandle;
+
+use crate::aggregate::{aggregate, Aggregate, Reading};
+use crate::auth::AuthUser;
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::freeze::alert_on_breaker;
+use crate::models::{now_ms, AggregationMethod, Price, Rounding, UsdAmount};
+use crate::rate_limit::RateLimiter;
+use crate::realtime::Broadcaster;
+use crate::sources::{PegSource, SourceDeps, SourceSpec, REDACTED};
+
+/// Latest reading of each source, by mint and then peg id.
+type Readings = Arc<DashMap<String, BTreeMap<String, Reading>>>;
+
+struct PegTask {
+	spec: SourceSpec,
+	handle: JoinHandle<()>,
+}
+
+/// Health of one running peg, as reported by `GET /pegs/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PegStatus {
+	pub id: String,
+	pub mint: String,
+	pub source: String,
+	pub kind: &'static str,
+	/// `pending` until the first fetch, then `ok` or `failing`
+	pub status: &'static str,
+	pub consecutive_failures: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_success_at: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_error: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_error_at: Option<String>,
+	/// Last fetched value before `invert`/`multiplier` and rounding
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_raw_value: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_latency_ms: Option<u64>,
+	/// Prices written after this source's readings
+	pub writes: u64,
+	/// Readings that did not write because of `deviation_bps`/`heartbeat_secs`
+	pub skipped_ticks: u64,
+}
+
+/// Polls the sources stored in `peg_sources`, one task per peg. Each tick skips frozen mints, fetches
+/// the price and rounds it to the spec's scale, then aggregates it with the mint's other readings and
+/// writes the result as `pegger`, keeping the mint's symbol and decimals.
+#[derive(Clone)]
+pub struct Pegger {
+	deps: SourceDeps,
+	db: DbState,
+	bc: Broadcaster,
+	readings: Readings,
+	tasks: Arc<Mutex<HashMap<String, PegTask>>>,
+	status: Arc<DashMap<String, PegStatus>>,
+}
+
+impl Pegger {
+	pub fn new(deps: SourceDeps, db: DbState, bc: Broadcaster) -> Self {
+		Self { deps, db, bc, readings: Arc::new(DashMap::new()), tasks: Arc::new(Mutex::new(HashMap::new())), status: Arc::new(DashMap::new()) }
+	}
+
+	/// Status of every running peg, by mint.
+	pub fn status(&self) -> Vec<PegStatus> {
+		let mut out: Vec<PegStatus> = self.status.iter().map(|s| s.clone()).collect();
+		out.sort_by(|a, b| a.mint.cmp(&b.mint).then_with(|| a.id.cmp(&b.id)));
+		out
+	}
+
+	/// Checks that `spec` can be built into a source and that its weight counts under the mint's aggregation method.
+	pub fn validate(&self, spec: &SourceSpec) -> AppResult<()> {
+		if spec.weight == 0 && self.db.get_config()?.aggregation_for(&spec.mint).method == AggregationMethod::WeightedMean {
+			return Err(AppError::BadRequest(format!("weight must be at least 1: {} is aggregated with weighted_mean", spec.mint)));
+		}
+		spec.build(&self.deps).map(|_| ())
+	}
+
+	/// Brings the running tasks in line with `peg_sources`: pegs that were removed or changed are
+	/// stopped (dropping their last reading) and new or changed ones are started.
+	pub fn reload(&self) -> AppResult<()> {
+		let pegs = self.db.list_pegs(None)?;
+		let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+		tasks.retain(|id, task| {
+			let keep = pegs.iter().any(|p| &p.id == id && p.spec == task.spec);
+			if !keep {
+				task.handle.abort();
+				self.status.remove(id);
+				if let Some(mut latest) = self.readings.get_mut(&task.spec.mint) { latest.remove(id); }
+			}
+			keep
+		});
+		for peg in pegs {
+			if tasks.contains_key(&peg.id) { continue; }
+			match self.spawn(&peg) {
+				Ok(handle) => { tasks.insert(peg.id.clone(), PegTask { spec: peg.spec, handle }); }
+				Err(e) => tracing::warn!("pegger: skipping peg {} for {}: {}", peg.id, peg.spec.mint, e),
+			}
+		}
+		Ok(())
+	}
+
+	fn spawn(&self, peg: &PegSource) -> AppResult<JoinHandle<()>> {
+		let source = peg.spec.build(&self.deps)?;
+		let (id, spec, label) = (peg.id.clone(), peg.spec.clone(), peg.spec.label());
+		self.status.insert(id.clone(), PegStatus {
+			id: id.clone(),
+			mint: spec.mint.clone(),
+			source: label.clone(),
+			kind: source.kind(),
+			status: "pending",
+			consecutive_failures: 0,
+			last_success_at: None,
+			last_error: None,
+			last_error_at: None,
+			last_raw_value: None,
+			last_latency_ms: None,
+			writes: 0,
+			skipped_ticks: 0,
+		});
+		let this = self.clone();
+		// each peg polls on its own task, so a slow upstream only delays itself
+		Ok(tokio::spawn(async move {
+			let interval = Duration::from_secs(spec.interval_secs);
+			let timeout = Duration::from_millis(spec.timeout_ms);
+			let mut failures = 0u32;
+			loop {
+				let started = Instant::now();
+				if !this.db.is_frozen(&spec.mint) {
+					let fetched = tokio::time::timeout(timeout, source.fetch_quote(&spec.mint))
+						.await
+						.unwrap_or_else(|_| Err(AppError::NoPrice(format!("timed out after {}ms", spec.timeout_ms))));
+					let latency_ms = started.elapsed().as_millis() as u64;
+					let transformed = fetched.and_then(|quote| {
+						let amount = spec.transform(quote.price)?;
+						let conf = quote.conf.map(|c| spec.transform_conf(quote.price, c, amount)).transpose()?;
+						Ok((quote.price, amount, conf))
+					});
+					match transformed {
+						Ok((raw, amount, conf)) => {
+							failures = 0;
+							this.record_success(&id, raw, latency_ms);
+							let snapshot: Vec<Reading> = {
+								let mut latest = this.readings.entry(spec.mint.clone()).or_default();
+								latest.insert(id.clone(), Reading { source: label.clone(), kind: source.kind(), amount, weight: spec.weight, conf, read_at_ms: now_ms() });
+								latest.values().cloned().collect()
+							};
+							this.write_aggregate(&id, &spec, &snapshot);
+						}
+						Err(e) => {
+							failures += 1;
+							this.record_failure(&id, &e, latency_ms);
+						}
+					}
+				}
+				let wait = if failures == 0 { interval.saturating_sub(started.elapsed()) } else { spec.retry_delay(failures) };
+				tokio::time::sleep(wait).await;
+			}
+		}))
+	}
+
+	fn record_success(&self, id: &str, raw: UsdAmount, latency_ms: u64) {
+		let Some(mut st) = self.status.get_mut(id) else { return };
+		let recovered = st.consecutive_failures > 0;
+		st.status = "ok";
+		st.consecutive_failures = 0;
+		st.last_success_at = Some(Price::now_iso());
+		st.last_raw_value = Some(raw.to_decimal_string());
+		st.last_latency_ms = Some(latency_ms);
+		if recovered {
+			tracing::info!("pegger: {} source for {} recovered", st.source, st.mint);
+			self.bc.publish(json!({"type":"peg_error","failing": false,"status": *st}));
+		}
+	}
+
+	/// Logs and publishes `peg_error` only when the source goes from healthy (or pending) to failing;
+	/// repeated failures just bump the counter.
+	fn record_failure(&self, id: &str, err: &AppError, latency_ms: u64) {
+		let Some(mut st) = self.status.get_mut(id) else { return };
+		st.status = "failing";
+		st.consecutive_failures += 1;
+		st.last_error = Some(err.to_string());
+		st.last_error_at = Some(Price::now_iso());
+		st.last_latency_ms = Some(latency_ms);
+		if st.consecutive_failures == 1 {
+			tracing::warn!("pegger: {} source for {} failed: {}", st.source, st.mint, err);
+			self.bc.publish(json!({"type":"peg_error","failing": true,"status": *st}));
+		} else {
+			tracing::debug!("pegger: {} source for {} still failing ({}x): {}", st.source, st.mint, st.consecutive_failures, err);
+		}
+	}
+
+	/// Aggregates the mint's readings and writes the result if `spec`'s write rule allows it.
+	fn write_aggregate(&self, id: &str, spec: &SourceSpec, readings: &[Reading]) {
+		let mint = spec.mint.as_str();
+		let policy = match self.db.get_config() {
+			Ok(cfg) => cfg.aggregation_for(mint),
+			Err(_) => return,
+		};
+		let Aggregate { amount, conf, report } = match aggregate(readings, &policy, now_ms()) {
+			Ok(v) => v,
+			Err(e) => { tracing::debug!("pegger: not writing {}: {}", mint, e); return; }
+		};
+		let current = match self.db.get_price(mint) {
+			Ok(p) => Some(p),
+			Err(AppError::NotFound) => None,
+			Err(_) => return,
+		};
+		if !spec.should_write(current.as_ref(), &amount, now_ms()) {
+			if let Some(mut st) = self.status.get_mut(id) { st.skipped_ticks += 1; }
+			return;
+		}
+		let mut price = Price::next_from(current.as_ref(), mint, amount, "pegger");
+		price.conf_mantissa = match conf.map(|c| c.rescale(price.usd_scale, Rounding::Ceil)).transpose() {
+			Ok(conf) => conf.map(|c| c.mantissa().to_string()),
+			Err(e) => { tracing::debug!("pegger: dropping confidence for {}: {}", mint, e); None }
+		};
+		match self.db.upsert_aggregated_price(&price, "pegger", &report) {
+			Ok(saved) => {
+				if let Some(mut st) = self.status.get_mut(id) { st.writes += 1; }
+				self.bc.publish(json!({"type":"price_upsert","price": saved}));
+			}
+			Err(e) => alert_on_breaker(&self.bc, &e),
+		}
+	}
+}
+
+/// Normalizes the mint and checks the spec builds before it is stored.
+fn prepare_spec(db: &DbState, pegger: &Pegger, mut spec: SourceSpec) -> AppResult<SourceSpec> {
+	spec.mint = db.resolve_mint(&spec.mint)?;
+	pegger.validate(&spec)?;
+	Ok(spec)
+}
+
+#[post("/pegs", data = "<body>")]
+pub fn create_peg(
+	user: AuthUser,
+	db: &State<DbState>,
+	bc: &State<Broadcaster>,
+	limiter: &State<RateLimiter>,
+	pegger: &State<Pegger>,
+	body: Json<SourceSpec>,
+) -> AppResult<(Status, Json<PegSource>)> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let spec = prepare_spec(db, pegger, body.into_inner())?;
+	let saved = db.insert_peg(&spec, &user.subject)?;
+	pegger.reload()?;
+	bc.publish(json!({"type":"peg_create","peg": saved}));
+	Ok((Status::Created, Json(saved)))
+}
+
+#[get("/pegs?<mint>")]
+pub fn list_pegs(user: AuthUser, db: &State<DbState>, mint: Option<&str>) -> AppResult<Json<Vec<PegSource>>> {
+	user.require_admin()?;
+	let mint = mint.map(|m| db.resolve_mint(m)).transpose()?;
+	Ok(Json(db.list_pegs(mint.as_deref())?))
+}
+
+#[get("/pegs/status")]
+pub fn peg_status(user: AuthUser, pegger: &State<Pegger>) -> AppResult<Json<Vec<PegStatus>>> {
+	user.require_admin()?;
+	Ok(Json(pegger.status()))
+}
+
+#[get("/pegs/<id>")]
+pub fn get_peg(user: AuthUser, db: &State<DbState>, id: &str) -> AppResult<Json<PegSource>> {
+	user.require_admin()?;
+	Ok(Json(db.get_peg(id)?))
+}
+
+/// Merges `fields` over `current` (`null` removes a field). Values equal to [`REDACTED`], as echoed back
+/// from a GET, keep what is stored, so a round trip never overwrites real header secrets.
+fn merge_patch(current: &SourceSpec, fields: &serde_json::Map<String, serde_json::Value>) -> AppResult<SourceSpec> {
+	let mut merged = serde_json::to_value(current)?;
+	if let Some(obj) = merged.as_object_mut() {
+		for (k, v) in fields {
+			if v.as_str() == Some(REDACTED) { continue; }
+			if v.is_null() { obj.remove(k); continue; }
+			let mut v = v.clone();
+			if let (true, Some(headers)) = (k == "headers", v.as_object_mut()) {
+				for (name, value) in headers.iter_mut() {
+					if value.as_str() != Some(REDACTED) { continue; }
+					let stored = current.header(name).ok_or_else(|| AppError::BadRequest(format!("header {} has no stored value to keep", name)))?;
+					*value = stored.into();
+				}
+			}
+			obj.insert(k.clone(), v);
+		}
+	}
+	serde_json::from_value(merged).map_err(|e| AppError::BadRequest(format!("invalid peg: {}", e)))
+}
+
+/// Merges the patch's top-level fields into the stored spec (see [`merge_patch`]) and revalidates it.
+#[patch("/pegs/<id>", data = "<patch>")]
+pub fn update_peg(
+	user: AuthUser,
+	db: &State<DbState>,
+	bc: &State<Broadcaster>,
+	limiter: &State<RateLimiter>,
+	pegger: &State<Pegger>,
+	id: &str,
+	patch: Json<serde_json::Value>,
+) -> AppResult<Json<PegSource>> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let current = db.get_peg(id)?;
+	let fields = patch.as_object().ok_or_else(|| AppError::BadRequest("patch must be a JSON object".into()))?;
+	let spec = prepare_spec(db, pegger, merge_patch(&current.spec, fields)?)?;
+	let saved = db.update_peg(id, &spec, &user.subject)?;
+	pegger.reload()?;
+	bc.publish(json!({"type":"peg_update","peg": saved}));
+	Ok(Json(saved))
+}
+
+#[delete("/pegs/<id>")]
+pub fn delete_peg(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, pegger: &State<Pegger>, id: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = db.delete_peg(id, &user.subject)?;
+	pegger.reload()?;
+	bc.publish(json!({"type":"peg_delete","peg": removed}));
+	Ok(Status::NoContent)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn http_spec() -> SourceSpec {
+		serde_json::from_value(json!({
+			"mint": "m", "type": "http_json", "url": "https://example.com", "path": "price",
+			"headers": { "Authorization": "Bearer secret", "X-Api-Key": "key" },
+		}))
+		.unwrap()
+	}
+
+	fn patch(v: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+		v.as_object().unwrap().clone()
+	}
+
+	#[test]
+	fn redacted_round_trip_keeps_secrets() {
+		let current = http_spec();
+		let mut echoed = serde_json::to_value(current.redacted()).unwrap();
+		echoed["url"] = "https://example.com/v2".into();
+		let merged = merge_patch(&current, echoed.as_object().unwrap()).unwrap();
+		assert_eq!(merged.header("authorization"), Some("Bearer secret"));
+		assert_eq!(merged.header("X-Api-Key"), Some("key"));
+		assert_eq!(serde_json::to_value(&merged).unwrap()["url"], "https://example.com/v2");
+	}
+
+	#[test]
+	fn patch_can_replace_and_drop_headers() {
+		let current = http_spec();
+		let merged = merge_patch(&current, &patch(json!({ "headers": { "Authorization": "Bearer new", "X-Api-Key": REDACTED } }))).unwrap();
+		assert_eq!(merged.header("Authorization"), Some("Bearer new"));
+		assert_eq!(merged.header("X-Api-Key"), Some("key"));
+		let merged = merge_patch(&current, &patch(json!({ "headers": { "Authorization": REDACTED } }))).unwrap();
+		assert_eq!(merged.header("X-Api-Key"), None);
+		assert!(merge_patch(&current, &patch(json!({ "headers": { "X-Other": REDACTED } }))).is_err());
+		assert_eq!(merge_patch(&current, &patch(json!({ "headers": null }))).unwrap().header("Authorization"), None);
+	}
+}
@@ -0,0 +1,213 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::UsdAmount;
+
+const MAGIC: u32 = 0xa1b2_c3d4;
+const VERSION: u32 = 2;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const STATUS_TRADING: u32 = 1;
+
+// byte offsets in a v2 price account
+const EXPO: usize = 20;
+const AGG_PRICE: usize = 208;
+const AGG_CONF: usize = 216;
+const AGG_STATUS: usize = 224;
+const AGG_PUB_SLOT: usize = 232;
+const MIN_LEN: usize = 240;
+
+/// Aggregate price of a Pyth price account: `price ± conf` times `10^expo`, as of `publish_slot`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PythPrice {
+	pub price: i64,
+	pub conf: u64,
+	pub expo: i32,
+	/// 0 unknown, 1 trading, 2 halted, 3 auction, 4 ignored
+	pub status: u32,
+	pub publish_slot: u64,
+}
+
+impl PythPrice {
+	/// Decodes the raw bytes of a v2 price account (after base64 decoding). Rejects anything that is
+	/// not a price account, but not prices that are stale or not trading; see [`PythPrice::amounts`].
+	pub fn decode(data: &[u8]) -> AppResult<Self> {
+		let bad = |msg: String| AppError::NoPrice(format!("not a Pyth price account: {}", msg));
+		if data.len() < MIN_LEN { return Err(bad(format!("{} bytes, expected at least {}", data.len(), MIN_LEN))); }
+		let magic = read_u32(data, 0);
+		if magic != MAGIC { return Err(bad(format!("magic {:#x}", magic))); }
+		let version = read_u32(data, 4);
+		if version != VERSION { return Err(bad(format!("version {}", version))); }
+		let account_type = read_u32(data, 8);
+		if account_type != ACCOUNT_TYPE_PRICE { return Err(bad(format!("account type {}", account_type))); }
+		Ok(Self {
+			price: read_u64(data, AGG_PRICE) as i64,
+			conf: read_u64(data, AGG_CONF),
+			expo: read_u32(data, EXPO) as i32,
+			status: read_u32(data, AGG_STATUS),
+			publish_slot: read_u64(data, AGG_PUB_SLOT),
+		})
+	}
+
+	/// Price and confidence as exact amounts at scale `-expo`. Fails unless the price is trading and positive.
+	pub fn amounts(&self) -> AppResult<(UsdAmount, UsdAmount)> {
+		if self.status != STATUS_TRADING {
+			return Err(AppError::NoPrice(format!("pyth price is {}, not trading", status_name(self.status))));
+		}
+		if self.price <= 0 { return Err(AppError::NoPrice(format!("pyth price {} is not positive", self.price))); }
+		let scaled = |value: u64| UsdAmount::from_decimal_str(&format!("{}e{}", value, self.expo));
+		Ok((scaled(self.price as u64)?, scaled(self.conf)?))
+	}
+}
+
+fn status_name(status: u32) -> &'static str {
+	match status {
+		0 => "unknown",
+		1 => "trading",
+		2 => "halted",
+		3 => "auction",
+		4 => "ignored",
+		_ => "in an unrecognized state",
+	}
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+	u32::from_le_bytes(data[at..at + 4].try_into().expect("4 bytes"))
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+	u64::from_le_bytes(data[at..at + 8].try_into().expect("8 bytes"))
+}
+
+/// Fetches `account` with `getAccountInfo` (base64) and returns the slot the RPC node answered at
+/// together with the decoded price.
+pub async fn fetch_price_account(client: &reqwest::Client, rpc_url: &str, account: &str) -> AppResult<(u64, PythPrice)> {
+	let req = serde_json::json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": "getAccountInfo",
+		"params": [account, { "encoding": "base64", "commitment": "confirmed" }]
+	});
+	let resp = client.post(rpc_url).json(&req).send().await.map_err(|e| AppError::Anyhow(e.into()))?;
+	let status = resp.status();
+	if !status.is_success() { return Err(AppError::NoPrice(format!("solana rpc returned {}", status))); }
+	let val: serde_json::Value = resp.json().await.map_err(|e| AppError::Anyhow(e.into()))?;
+	if let Some(err) = val.get("error") {
+		let message = err.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+		return Err(AppError::NoPrice(format!("solana rpc error for {}: {}", account, message)));
+	}
+	let slot = val.pointer("/result/context/slot").and_then(|s| s.as_u64()).ok_or_else(|| AppError::NoPrice(format!("solana rpc gave no context slot for {}", account)))?;
+	let encoded = match val.pointer("/result/value") {
+		Some(serde_json::Value::Null) | None => return Err(AppError::NoPrice(format!("pyth account {} not found", account))),
+		Some(value) => value.pointer("/data/0").and_then(|d| d.as_str()).ok_or_else(|| AppError::NoPrice(format!("no base64 data for {}", account)))?,
+	};
+	let data = STANDARD.decode(encoded).map_err(|e| AppError::NoPrice(format!("invalid base64 data for {}: {}", account, e)))?;
+	Ok((slot, PythPrice::decode(&data)?))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use std::io::{BufRead, BufReader, Read, Write};
+
+	/// Raw v2 price account with the given aggregate fields.
+	pub(crate) fn account(price: i64, conf: u64, expo: i32, status: u32, publish_slot: u64) -> Vec<u8> {
+		let mut data = vec![0u8; MIN_LEN];
+		data[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+		data[4..8].copy_from_slice(&VERSION.to_le_bytes());
+		data[8..12].copy_from_slice(&ACCOUNT_TYPE_PRICE.to_le_bytes());
+		data[EXPO..EXPO + 4].copy_from_slice(&expo.to_le_bytes());
+		data[AGG_PRICE..AGG_PRICE + 8].copy_from_slice(&price.to_le_bytes());
+		data[AGG_CONF..AGG_CONF + 8].copy_from_slice(&conf.to_le_bytes());
+		data[AGG_STATUS..AGG_STATUS + 4].copy_from_slice(&status.to_le_bytes());
+		data[AGG_PUB_SLOT..AGG_PUB_SLOT + 8].copy_from_slice(&publish_slot.to_le_bytes());
+		data
+	}
+
+	/// `getAccountInfo` result at `slot` holding `data`.
+	pub(crate) fn account_info(slot: u64, data: &[u8]) -> serde_json::Value {
+		serde_json::json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"result": { "context": { "slot": slot }, "value": { "data": [STANDARD.encode(data), "base64"], "owner": "", "lamports": 1 } }
+		})
+	}
+
+	/// Local JSON-RPC server answering every request with `body`. Returns its URL.
+	pub(crate) fn mock_rpc(body: serde_json::Value) -> String {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		let body = body.to_string();
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				let Ok(mut stream) = stream else { continue };
+				let mut reader = BufReader::new(stream.try_clone().unwrap());
+				let mut content_length = 0;
+				let mut line = String::new();
+				while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+					if let Some((name, value)) = line.split_once(':') {
+						if name.eq_ignore_ascii_case("content-length") { content_length = value.trim().parse().unwrap_or(0); }
+					}
+					line.clear();
+				}
+				let mut request = vec![0u8; content_length];
+				let _ = reader.read_exact(&mut request);
+				let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+			}
+		});
+		url
+	}
+
+	#[test]
+	fn decodes_trading_price() {
+		let price = PythPrice::decode(&account(6_512_345_678, 1_234_567, -8, STATUS_TRADING, 42)).unwrap();
+		assert_eq!(price, PythPrice { price: 6_512_345_678, conf: 1_234_567, expo: -8, status: STATUS_TRADING, publish_slot: 42 });
+		let (amount, conf) = price.amounts().unwrap();
+		assert_eq!((amount.mantissa(), amount.scale()), (6_512_345_678, 8));
+		assert_eq!((conf.mantissa(), conf.scale()), (1_234_567, 8));
+	}
+
+	#[test]
+	fn positive_expo_scales_up() {
+		let (amount, conf) = PythPrice::decode(&account(15, 2, 3, STATUS_TRADING, 1)).unwrap().amounts().unwrap();
+		assert_eq!((amount.mantissa(), amount.scale()), (15_000, 0));
+		assert_eq!((conf.mantissa(), conf.scale()), (2_000, 0));
+	}
+
+	#[test]
+	fn rejects_prices_not_trading() {
+		let price = PythPrice::decode(&account(100, 1, -2, 2, 1)).unwrap();
+		let err = price.amounts().unwrap_err().to_string();
+		assert!(err.contains("halted"), "{}", err);
+		assert!(PythPrice::decode(&account(-100, 1, -2, STATUS_TRADING, 1)).unwrap().amounts().is_err());
+	}
+
+	#[test]
+	fn rejects_other_accounts() {
+		let mut data = account(100, 1, -2, STATUS_TRADING, 1);
+		data[0] ^= 0xff;
+		assert!(PythPrice::decode(&data).unwrap_err().to_string().contains("magic"));
+		let short = account(100, 1, -2, STATUS_TRADING, 1);
+		assert!(PythPrice::decode(&short[..MIN_LEN - 1]).unwrap_err().to_string().contains("expected at least"));
+	}
+
+	#[tokio::test]
+	async fn fetches_over_rpc() {
+		let url = mock_rpc(account_info(50, &account(100, 1, -2, STATUS_TRADING, 48)));
+		let (slot, price) = fetch_price_account(&reqwest::Client::new(), &url, "acct").await.unwrap();
+		assert_eq!((slot, price.price, price.publish_slot), (50, 100, 48));
+	}
+
+	#[tokio::test]
+	async fn rpc_failures_are_errors() {
+		let client = reqwest::Client::new();
+		let missing = mock_rpc(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": { "context": { "slot": 50 }, "value": null } }));
+		assert!(fetch_price_account(&client, &missing, "acct").await.unwrap_err().to_string().contains("not found"));
+		let error = mock_rpc(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "Invalid param" } }));
+		assert!(fetch_price_account(&client, &error, "acct").await.unwrap_err().to_string().contains("Invalid param"));
+		let mut no_slot = account_info(50, &account(100, 1, -2, STATUS_TRADING, 48));
+		no_slot["result"].as_object_mut().unwrap().remove("context");
+		let no_slot = mock_rpc(no_slot);
+		assert!(fetch_price_account(&client, &no_slot, "acct").await.unwrap_err().to_string().contains("no context slot"));
+	}
+}
@@ -258,7 +258,7 @@ impl QuicknodeProxy {
 		true
 	}
 
-	fn clone_shallow(&self) -> Self {
+	pub(crate) fn clone_shallow(&self) -> Self {
 		Self {
 			client: self.client.clone(),
 			base_url: self.base_url.clone(),
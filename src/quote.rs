@@ -0,0 +1,324 @@
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::models::{div_round, pow10, Price, Rounding, UsdAmount, MAX_SCALE};
+use crate::routes::apply_freshness;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteValue {
+	pub mint: String,
+	pub raw_amount: String,
+	pub decimals: u8,
+	#[serde(flatten)]
+	pub value: UsdAmount,
+	/// `value` as a human decimal string
+	pub usd: String,
+	pub price: UsdAmount,
+	pub price_updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum QuoteValueItem {
+	Ok(QuoteValue),
+	Err { mint: String, raw_amount: String, error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteValueBatch {
+	pub items: Vec<QuoteValueItem>,
+	/// Sum of every item that could be valued
+	pub total: UsdAmount,
+	pub total_usd: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteConvert {
+	pub from: String,
+	pub to: String,
+	pub raw_amount: String,
+	/// Target raw units after the fee
+	pub out_raw_amount: String,
+	/// Target raw units before the fee
+	pub gross_out_raw_amount: String,
+	pub fee_raw_amount: String,
+	pub fee_bps: u16,
+	pub rounding: Rounding,
+	pub from_price: UsdAmount,
+	pub to_price: UsdAmount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteValueRequest {
+	mint: String,
+	raw_amount: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteValueBatchBody {
+	items: Vec<QuoteValueRequest>,
+	#[serde(default)]
+	scale: Option<u32>,
+	#[serde(default)]
+	rounding: Option<Rounding>,
+}
+
+/// Parses an on-chain base-unit amount (unsigned integer string).
+pub fn parse_raw_amount(raw: &str) -> AppResult<u128> {
+	let t = raw.trim();
+	if t.is_empty() || !t.bytes().all(|b| b.is_ascii_digit()) {
+		return Err(AppError::BadRequest(format!("raw_amount must be a non-negative integer string, got {:?}", raw)));
+	}
+	t.parse::<u128>().map_err(|_| AppError::BadRequest("raw_amount out of range".into()))
+}
+
+pub fn price_decimals(price: &Price) -> AppResult<u8> {
+	price.decimals.ok_or_else(|| AppError::BadRequest(format!("{} has no decimals configured", price.mint)))
+}
+
+/// USD value of `raw_amount` base units; exact unless `scale` is given, in which case it is rounded with `rounding`.
+pub fn value_of(price: &Price, raw_amount: &str, scale: Option<u32>, rounding: Rounding) -> AppResult<QuoteValue> {
+	let decimals = price_decimals(price)?;
+	let amount = UsdAmount::new(parse_raw_amount(raw_amount)?, decimals as u32)?;
+	let unit_price = price.usd_amount()?;
+	if decimals as u32 + unit_price.scale() > MAX_SCALE {
+		return Err(AppError::BadRequest(format!(
+			"{} decimals ({}) + usd_scale ({}) exceeds {}; cannot value exactly",
+			price.mint, decimals, unit_price.scale(), MAX_SCALE
+		)));
+	}
+	let exact = amount
+		.checked_mul(&unit_price)
+		.ok_or_else(|| AppError::BadRequest("value overflows; use a smaller raw_amount".into()))?;
+	let value = match scale {
+		Some(s) => exact.rescale(s, rounding)?,
+		None => exact.normalize(),
+	};
+	Ok(QuoteValue {
+		mint: price.mint.clone(),
+		raw_amount: raw_amount.trim().to_string(),
+		decimals,
+		value,
+		usd: value.to_decimal_string(),
+		price: unit_price,
+		price_updated_at: price.updated_at.clone(),
+	})
+}
+
+/// Raw units of `to` worth `raw_amount` raw units of `from`, net of `fee_bps`.
+/// Evaluated as one rational `raw * p_from * 10^dec_to * (10000 - fee) / (p_to * 10^dec_from * 10000)` so rounding happens once.
+pub fn convert(from: &Price, to: &Price, raw_amount: &str, fee_bps: u16, rounding: Rounding) -> AppResult<QuoteConvert> {
+	if fee_bps > 10_000 { return Err(AppError::BadRequest("fee_bps must be <= 10000".into())); }
+	let raw = parse_raw_amount(raw_amount)?;
+	let (dec_from, dec_to) = (price_decimals(from)?, price_decimals(to)?);
+	let (p_from, p_to) = (from.usd_amount()?, to.usd_amount()?);
+	if p_to.is_zero() { return Err(AppError::BadRequest(format!("{} has a zero price", to.mint))); }
+	let overflow = || AppError::BadRequest("conversion overflows; use a smaller raw_amount".into());
+	let exp = (dec_to as i64 + p_to.scale() as i64) - (dec_from as i64 + p_from.scale() as i64);
+	let ten_exp = pow10(exp.unsigned_abs() as u32).ok_or_else(overflow)?;
+	let ratio = |keep_bps: u128| -> AppResult<u128> {
+		let mut num = raw.checked_mul(p_from.mantissa()).and_then(|v| v.checked_mul(keep_bps)).ok_or_else(overflow)?;
+		let mut den = p_to.mantissa().checked_mul(10_000).ok_or_else(overflow)?;
+		if exp >= 0 { num = num.checked_mul(ten_exp).ok_or_else(overflow)?; } else { den = den.checked_mul(ten_exp).ok_or_else(overflow)?; }
+		Ok(div_round(num, den, rounding))
+	};
+	let gross = ratio(10_000)?;
+	let net = ratio(10_000 - fee_bps as u128)?;
+	Ok(QuoteConvert {
+		from: from.mint.clone(),
+		to: to.mint.clone(),
+		raw_amount: raw.to_string(),
+		out_raw_amount: net.to_string(),
+		gross_out_raw_amount: gross.to_string(),
+		fee_raw_amount: gross.saturating_sub(net).to_string(),
+		fee_bps,
+		rounding,
+		from_price: p_from,
+		to_price: p_to,
+	})
+}
+
+/// Current price for a mint or `symbol:` key, refusing frozen mints (409) and stale prices (422).
+fn quotable_price(db: &DbState, key: &str) -> AppResult<Price> {
+	let mut price = db.get_price(&db.resolve_mint(key)?)?;
+	if db.get_frozen(&price.mint)?.is_some() {
+		return Err(AppError::Conflict(format!("{} is frozen; refusing to quote", price.mint)));
+	}
+	apply_freshness(db, std::iter::once(&mut price), false)?;
+	if price.stale == Some(true) {
+		let age = price.age_secs.map(|a| format!("{}s old", a)).unwrap_or_else(|| "unknown age".into());
+		return Err(AppError::Unprocessable(format!("{} price is stale ({}); refusing to quote", price.mint, age)));
+	}
+	Ok(price)
+}
+
+fn parse_rounding(rounding: Option<&str>) -> AppResult<Rounding> {
+	rounding.map(Rounding::parse).transpose().map(|r| r.unwrap_or_default())
+}
+
+#[get("/quote/value?<mint>&<raw_amount>&<scale>&<rounding>")]
+pub fn quote_value(db: &State<DbState>, mint: &str, raw_amount: &str, scale: Option<u32>, rounding: Option<&str>) -> AppResult<Json<QuoteValue>> {
+	let rounding = parse_rounding(rounding)?;
+	let price = quotable_price(db, mint)?;
+	Ok(Json(value_of(&price, raw_amount, scale, rounding)?))
+}
+
+#[get("/quote/convert?<from>&<to>&<raw_amount>&<rounding>&<fee_bps>")]
+pub fn quote_convert(db: &State<DbState>, from: &str, to: &str, raw_amount: &str, rounding: Option<&str>, fee_bps: Option<u16>) -> AppResult<Json<QuoteConvert>> {
+	let rounding = parse_rounding(rounding)?;
+	let fee_bps = match fee_bps {
+		Some(f) => f,
+		None => db.get_config()?.fee_bps_default,
+	};
+	let (from, to) = (quotable_price(db, from)?, quotable_price(db, to)?);
+	Ok(Json(convert(&from, &to, raw_amount, fee_bps, rounding)?))
+}
+
+#[post("/quote/value", data = "<body>")]
+pub fn quote_value_batch(db: &State<DbState>, body: Json<QuoteValueBatchBody>) -> AppResult<Json<QuoteValueBatch>> {
+	if body.items.len() > 500 { return Err(AppError::BadRequest("at most 500 items per request".into())); }
+	let rounding = body.rounding.unwrap_or_default();
+	let mut items = Vec::with_capacity(body.items.len());
+	let mut total = UsdAmount::zero();
+	for req in &body.items {
+		let res = quotable_price(db, &req.mint).and_then(|p| value_of(&p, &req.raw_amount, body.scale, rounding));
+		match res {
+			Ok(v) => {
+				total = total
+					.checked_add(&v.value)
+					.ok_or_else(|| AppError::BadRequest("portfolio total overflows".into()))?;
+				items.push(QuoteValueItem::Ok(v));
+			}
+			Err(e) => items.push(QuoteValueItem::Err { mint: req.mint.clone(), raw_amount: req.raw_amount.clone(), error: e.to_string() }),
+		}
+	}
+	let total = total.normalize();
+	Ok(Json(QuoteValueBatch { items, total, total_usd: total.to_decimal_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn price(mint: &str, mantissa: u128, scale: u32, decimals: u8) -> Price {
+		let mut p = Price::next_from(None, mint, UsdAmount::new(mantissa, scale).unwrap(), "test");
+		p.decimals = Some(decimals);
+		p
+	}
+
+	fn usd(price: &Price, raw: &str, scale: Option<u32>, rounding: Rounding) -> String {
+		value_of(price, raw, scale, rounding).unwrap().usd
+	}
+
+	#[test]
+	fn value_is_exact_without_scale() {
+		// 1.5 tokens at $1.25
+		let p = price("A", 125, 2, 6);
+		assert_eq!(usd(&p, "1500000", None, Rounding::Floor), "1.875");
+		assert_eq!(usd(&p, "0", None, Rounding::Floor), "0");
+	}
+
+	#[test]
+	fn value_rounds_per_mode() {
+		let p = price("A", 125, 2, 6);
+		assert_eq!(usd(&p, "1500000", Some(2), Rounding::Floor), "1.87");
+		assert_eq!(usd(&p, "1500000", Some(2), Rounding::Ceil), "1.88");
+		assert_eq!(usd(&p, "1500000", Some(2), Rounding::HalfEven), "1.88");
+		// 1.625 is a tie that goes down to the even digit
+		assert_eq!(usd(&p, "1300000", Some(2), Rounding::HalfEven), "1.62");
+	}
+
+	#[test]
+	fn value_scale_truncates_and_pads() {
+		let p = price("A", 125, 2, 6);
+		assert_eq!(usd(&p, "1500000", Some(0), Rounding::Floor), "1");
+		assert_eq!(usd(&p, "1500000", Some(0), Rounding::Ceil), "2");
+		assert_eq!(usd(&p, "1", Some(2), Rounding::Floor), "0.00");
+		assert_eq!(usd(&p, "1500000", Some(5), Rounding::Floor), "1.87500");
+		assert!(value_of(&p, "1500000", Some(MAX_SCALE + 1), Rounding::Floor).is_err());
+	}
+
+	#[test]
+	fn value_overflow_and_scale_bound_are_distinct() {
+		let err = value_of(&price("A", 2, 0, 0), &u128::MAX.to_string(), None, Rounding::Floor).unwrap_err();
+		assert!(err.to_string().contains("overflows"), "{}", err);
+		let err = value_of(&price("A", 1, 10, 30), "1", None, Rounding::Floor).unwrap_err();
+		assert!(err.to_string().contains("usd_scale"), "{}", err);
+		assert!(value_of(&price("A", 1, 8, 30), "1", None, Rounding::Floor).is_ok());
+	}
+
+	#[test]
+	fn value_rejects_bad_input() {
+		let p = price("A", 125, 2, 6);
+		for raw in ["", "-1", "1.5", "abc"] {
+			assert!(value_of(&p, raw, None, Rounding::Floor).is_err(), "{:?}", raw);
+		}
+		let mut no_decimals = p.clone();
+		no_decimals.decimals = None;
+		assert!(value_of(&no_decimals, "1", None, Rounding::Floor).is_err());
+	}
+
+	fn out(q: &QuoteConvert) -> (&str, &str, &str) {
+		(&q.gross_out_raw_amount, &q.out_raw_amount, &q.fee_raw_amount)
+	}
+
+	#[test]
+	fn convert_across_decimals() {
+		// A: $2.00 with 6 decimals, B: $0.5 with 9 decimals; exp = (9 + 1) - (6 + 2) = 2
+		let (a, b) = (price("A", 200, 2, 6), price("B", 5, 1, 9));
+		let q = convert(&a, &b, "1000000", 0, Rounding::Floor).unwrap();
+		assert_eq!(out(&q), ("4000000000", "4000000000", "0"));
+		// back the other way the exponent is negative: (6 + 2) - (9 + 1) = -2
+		let q = convert(&b, &a, "4000000000", 0, Rounding::Floor).unwrap();
+		assert_eq!(out(&q), ("1000000", "1000000", "0"));
+		// 1 raw unit of B is 1/4000 of a raw unit of A
+		assert_eq!(convert(&b, &a, "1", 0, Rounding::Floor).unwrap().out_raw_amount, "0");
+		assert_eq!(convert(&b, &a, "1", 0, Rounding::Ceil).unwrap().out_raw_amount, "1");
+	}
+
+	#[test]
+	fn convert_fee_bounds() {
+		let (a, b) = (price("A", 1, 0, 6), price("B", 1, 0, 6));
+		let q = convert(&a, &b, "1000000", 0, Rounding::Floor).unwrap();
+		assert_eq!(out(&q), ("1000000", "1000000", "0"));
+		let q = convert(&a, &b, "1000000", 10_000, Rounding::Floor).unwrap();
+		assert_eq!(out(&q), ("1000000", "0", "1000000"));
+		assert!(convert(&a, &b, "1000000", 10_001, Rounding::Floor).is_err());
+	}
+
+	#[test]
+	fn convert_rounds_once_after_fee() {
+		// 1000001 * 0.997 = 997000.997; the gross amount is exact
+		let (a, b) = (price("A", 1, 0, 6), price("B", 1, 0, 6));
+		let q = |r| convert(&a, &b, "1000001", 30, r).unwrap();
+		assert_eq!(out(&q(Rounding::Floor)), ("1000001", "997000", "3001"));
+		assert_eq!(out(&q(Rounding::Ceil)), ("1000001", "997001", "3000"));
+		assert_eq!(out(&q(Rounding::HalfEven)), ("1000001", "997001", "3000"));
+	}
+
+	#[test]
+	fn convert_rejects_zero_target_price_and_overflow() {
+		let a = price("A", 1, 0, 6);
+		assert!(convert(&a, &price("B", 0, 0, 6), "1", 0, Rounding::Floor).is_err());
+		let err = convert(&a, &price("B", 1, 0, 6), &u128::MAX.to_string(), 0, Rounding::Floor).unwrap_err();
+		assert!(err.to_string().contains("overflows"), "{}", err);
+	}
+
+	#[test]
+	fn quotes_refuse_stale_and_frozen_prices() {
+		let t = crate::db::tests::temp_db();
+		let mut old = crate::db::tests::price("OLD", None, "100");
+		old.updated_at = "2020-01-01T00:00:00Z".into();
+		t.db.upsert_price(&old, "test", false).unwrap();
+		t.db.upsert_price(&crate::db::tests::price("NEW", None, "100"), "test", false).unwrap();
+		assert!(quotable_price(&t.db, "OLD").is_ok());
+		t.db.update_config(serde_json::json!({"max_age_secs": 60}), "test").unwrap();
+		assert!(matches!(quotable_price(&t.db, "OLD"), Err(AppError::Unprocessable(_))));
+		assert!(quotable_price(&t.db, "NEW").is_ok());
+		t.db.freeze_mint("NEW", "test", "test", false).unwrap();
+		assert!(matches!(quotable_price(&t.db, "NEW"), Err(AppError::Conflict(_))));
+	}
+}
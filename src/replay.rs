@@ -0,0 +1,271 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use serde_json::json;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::freeze::alert_on_breaker;
+use crate::models::{format_ms, parse_rfc3339_ms, Price, UsdAmount};
+use crate::rate_limit::RateLimiter;
+use crate::realtime::Broadcaster;
+
+const MAX_ROWS: usize = 100_000;
+const MAX_UPLOAD_MIB: u64 = 16;
+/// Slowest playback accepted; below this the recorded gaps stop fitting in a `Duration`.
+const MIN_SPEED: f64 = 0.001;
+
+#[derive(Debug, Clone)]
+struct ReplayRow {
+	ts_ms: i64,
+	mint: String,
+	amount: UsdAmount,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayInfo {
+	pub id: String,
+	/// Playback rate; `1` is real time, `60` plays an hour in a minute
+	pub speed: f64,
+	pub rows: usize,
+	pub mints: Vec<String>,
+	pub first_ts: String,
+	pub last_ts: String,
+	/// `running`, `completed`, `failed` or `stopped`
+	pub status: String,
+	/// Rows written so far
+	pub applied: usize,
+	pub started_at: String,
+	pub started_by: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+struct ReplayRun {
+	info: ReplayInfo,
+	task: Option<JoinHandle<()>>,
+}
+
+/// Running and finished replays, kept in memory until deleted.
+#[derive(Clone, Default)]
+pub struct ReplayEngine {
+	runs: Arc<DashMap<String, ReplayRun>>,
+}
+
+impl ReplayEngine {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn list(&self) -> Vec<ReplayInfo> {
+		let mut out: Vec<ReplayInfo> = self.runs.iter().map(|r| r.info.clone()).collect();
+		out.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+		out
+	}
+
+	pub fn get(&self, id: &str) -> Option<ReplayInfo> {
+		self.runs.get(id).map(|r| r.info.clone())
+	}
+
+	/// Writes `rows` (sorted by time) through the normal upsert path, spacing them by their recorded
+	/// gaps divided by `info.speed`. The first row is written immediately.
+	fn start(&self, info: ReplayInfo, rows: Vec<ReplayRow>, db: DbState, bc: Broadcaster) -> AppResult<ReplayInfo> {
+		let id = info.id.clone();
+		self.runs.insert(id.clone(), ReplayRun { info: info.clone(), task: None });
+		let this = self.clone();
+		let task = tokio::spawn(async move {
+			let actor = format!("replay:{}", info.id);
+			let origin = tokio::time::Instant::now();
+			let first_ts = rows.first().map(|r| r.ts_ms).unwrap_or_default();
+			let mut outcome = Ok(());
+			for (i, row) in rows.iter().enumerate() {
+				let Some(at) = row.ts_ms.checked_sub(first_ts)
+					.and_then(|gap_ms| Duration::try_from_secs_f64(gap_ms as f64 / 1000.0 / info.speed).ok())
+					.and_then(|offset| origin.checked_add(offset))
+				else {
+					outcome = Err(AppError::BadRequest(format!("row {} is too far after the first row to schedule", i + 1)));
+					break;
+				};
+				tokio::time::sleep_until(at).await;
+				let current = match db.get_price(&row.mint) {
+					Ok(p) => Some(p),
+					Err(AppError::NotFound) => None,
+					Err(e) => { outcome = Err(e); break; }
+				};
+				let price = Price::next_from(current.as_ref(), &row.mint, row.amount, &actor);
+				match db.upsert_price(&price, &actor, false) {
+					Ok(saved) => bc.publish(json!({"type":"price_upsert","price": saved})),
+					Err(e) => { alert_on_breaker(&bc, &e); outcome = Err(e); break; }
+				}
+				if let Some(mut run) = this.runs.get_mut(&info.id) { run.info.applied = i + 1; }
+			}
+			if let Some(mut run) = this.runs.get_mut(&info.id) {
+				match outcome {
+					Ok(()) => run.info.status = "completed".into(),
+					Err(e) => {
+						run.info.status = "failed".into();
+						run.info.error = Some(e.to_string());
+					}
+				}
+				bc.publish(json!({"type":"replay_end","replay": run.info}));
+			}
+		});
+		if let Some(mut run) = self.runs.get_mut(&id) { run.task = Some(task); }
+		self.get(&id).ok_or(AppError::NotFound)
+	}
+
+	/// Aborts the replay if it is still running and forgets it.
+	pub fn remove(&self, id: &str) -> AppResult<ReplayInfo> {
+		let (_, run) = self.runs.remove(id).ok_or(AppError::NotFound)?;
+		let mut info = run.info;
+		if let Some(task) = run.task {
+			if !task.is_finished() {
+				task.abort();
+				info.status = "stopped".into();
+			}
+		}
+		Ok(info)
+	}
+}
+
+/// Parses CSV (`ts,mint,usd_mantissa,usd_scale`, header optional and may reorder columns) or JSONL
+/// (one `{"ts","mint","usd_mantissa","usd_scale"}` object per line). `ts` is RFC3339 or unix milliseconds.
+fn parse_rows(body: &str, jsonl: bool, db: &DbState) -> AppResult<Vec<ReplayRow>> {
+	let mut rows = Vec::new();
+	let mut columns = [0usize, 1, 2, 3];
+	for (n, line) in body.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') { continue; }
+		let bad = |msg: String| AppError::BadRequest(format!("line {}: {}", n + 1, msg));
+		let (ts, mint, mantissa, scale) = if jsonl {
+			let v: serde_json::Value = serde_json::from_str(line).map_err(|e| bad(e.to_string()))?;
+			let field = |k: &str| -> Result<String, AppError> {
+				match v.get(k) {
+					Some(serde_json::Value::String(s)) => Ok(s.clone()),
+					Some(serde_json::Value::Number(x)) => Ok(x.to_string()),
+					_ => Err(bad(format!("missing {}", k))),
+				}
+			};
+			(field("ts")?, field("mint")?, field("usd_mantissa")?, field("usd_scale")?)
+		} else {
+			let cells: Vec<&str> = line.split(',').map(|c| c.trim().trim_matches('"')).collect();
+			if rows.is_empty() && cells.iter().any(|c| c.eq_ignore_ascii_case("mint")) {
+				for (slot, name) in ["ts", "mint", "usd_mantissa", "usd_scale"].iter().enumerate() {
+					columns[slot] = cells.iter().position(|c| c.eq_ignore_ascii_case(name)).ok_or_else(|| bad(format!("header has no {} column", name)))?;
+				}
+				continue;
+			}
+			let cell = |i: usize| cells.get(columns[i]).map(|c| c.to_string()).ok_or_else(|| bad("expected ts,mint,usd_mantissa,usd_scale".into()));
+			(cell(0)?, cell(1)?, cell(2)?, cell(3)?)
+		};
+		let ts_ms = parse_rfc3339_ms(&ts)
+			.or_else(|| ts.parse::<i64>().ok())
+			.ok_or_else(|| bad(format!("ts {:?} is neither RFC3339 nor unix milliseconds", ts)))?;
+		let scale = scale.parse::<u32>().map_err(|_| bad(format!("invalid usd_scale {:?}", scale)))?;
+		let amount = UsdAmount::parse(&mantissa, scale).map_err(|e| match e {
+			AppError::BadRequest(msg) => bad(msg),
+			other => other,
+		})?;
+		rows.push(ReplayRow { ts_ms, mint: db.resolve_mint(&mint)?, amount });
+		if rows.len() > MAX_ROWS { return Err(AppError::BadRequest(format!("at most {} rows per replay", MAX_ROWS))); }
+	}
+	if rows.is_empty() { return Err(AppError::BadRequest("no rows to replay".into())); }
+	rows.sort_by_key(|r| r.ts_ms);
+	Ok(rows)
+}
+
+/// Managed state a replay writes through, grouped into one guard.
+pub struct ReplayDeps<'r> {
+	db: &'r DbState,
+	bc: &'r Broadcaster,
+	engine: &'r ReplayEngine,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ReplayDeps<'r> {
+	type Error = AppError;
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let rocket = req.rocket();
+		match (rocket.state::<DbState>(), rocket.state::<Broadcaster>(), rocket.state::<ReplayEngine>()) {
+			(Some(db), Some(bc), Some(engine)) => Outcome::Success(ReplayDeps { db, bc, engine }),
+			_ => Outcome::Error((Status::InternalServerError, AppError::Anyhow(anyhow::anyhow!("replay state is not managed")))),
+		}
+	}
+}
+
+/// Upload as `text/csv` or `application/x-ndjson` (or pass `format=csv|jsonl`); `speed` defaults to 1 (real time).
+#[post("/replays?<speed>&<format>", data = "<data>")]
+pub async fn start_replay(
+	user: AuthUser,
+	deps: ReplayDeps<'_>,
+	limiter: &State<RateLimiter>,
+	content_type: Option<&ContentType>,
+	speed: Option<f64>,
+	format: Option<&str>,
+	data: Data<'_>,
+) -> AppResult<(Status, Json<ReplayInfo>)> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let ReplayDeps { db, bc, engine } = deps;
+	let speed = speed.unwrap_or(1.0);
+	if !speed.is_finite() || speed < MIN_SPEED { return Err(AppError::BadRequest(format!("speed must be a number >= {}", MIN_SPEED))); }
+	let jsonl = match format {
+		Some(f) if f.eq_ignore_ascii_case("jsonl") || f.eq_ignore_ascii_case("ndjson") => true,
+		Some(f) if f.eq_ignore_ascii_case("csv") => false,
+		Some(f) => return Err(AppError::BadRequest(format!("unknown format {:?}; use csv or jsonl", f))),
+		None => content_type.map(|ct| ct.sub().as_str().contains("json")).unwrap_or(false),
+	};
+	let body = data.open(MAX_UPLOAD_MIB.mebibytes()).into_string().await.map_err(|e| AppError::BadRequest(e.to_string()))?;
+	if !body.is_complete() { return Err(AppError::BadRequest(format!("upload exceeds {} MiB", MAX_UPLOAD_MIB))); }
+	let rows = parse_rows(&body, jsonl, db)?;
+	let mut mints: Vec<String> = rows.iter().map(|r| r.mint.clone()).collect();
+	mints.sort();
+	mints.dedup();
+	let info = ReplayInfo {
+		id: Uuid::new_v4().to_string(),
+		speed,
+		rows: rows.len(),
+		mints,
+		first_ts: rows.first().map(|r| format_ms(r.ts_ms)).unwrap_or_default(),
+		last_ts: rows.last().map(|r| format_ms(r.ts_ms)).unwrap_or_default(),
+		status: "running".into(),
+		applied: 0,
+		started_at: Price::now_iso(),
+		started_by: user.subject.clone(),
+		error: None,
+	};
+	let started = engine.start(info, rows, db.clone(), bc.clone())?;
+	db.insert_audit("START_REPLAY", &user.subject, &started.id, None, Some(serde_json::to_value(&started)?))?;
+	bc.publish(json!({"type":"replay_start","replay": started}));
+	Ok((Status::Created, Json(started)))
+}
+
+#[get("/replays")]
+pub fn list_replays(engine: &State<ReplayEngine>) -> Json<Vec<ReplayInfo>> {
+	Json(engine.list())
+}
+
+#[get("/replays/<id>")]
+pub fn get_replay(engine: &State<ReplayEngine>, id: &str) -> AppResult<Json<ReplayInfo>> {
+	engine.get(id).map(Json).ok_or(AppError::NotFound)
+}
+
+#[delete("/replays/<id>")]
+pub fn delete_replay(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, engine: &State<ReplayEngine>, id: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = engine.remove(id)?;
+	db.insert_audit("STOP_REPLAY", &user.subject, &removed.id, Some(serde_json::to_value(&removed)?), None)?;
+	bc.publish(json!({"type":"replay_stop","replay": removed}));
+	Ok(Status::NoContent)
+}
@@ -1,14 +1,19 @@
-use rocket::http::Status;
+use rocket::http::{Header, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{FromRequest, Outcome as RequestOutcome, Request};
 use rocket::response::content::RawHtml;
 use rocket::serde::json::Json;
 use rocket::{Route, State};
 use serde::Deserialize;
 use serde_json::json;
+use std::collections::BTreeMap;
 
 use crate::auth::AuthUser;
+use crate::candles::{build_candles, Candle, Interval};
 use crate::db::DbState;
+use crate::freeze::alert_on_breaker;
 use crate::errors::{AppError, AppResult};
-use crate::models::{Config, PaginatedAuditResponse, Price, SymbolMap};
+use crate::models::{now_ms, parse_rfc3339_ms, Config, PaginatedAuditResponse, PaginatedPriceHistoryResponse, Price, PriceListResponse, PriceQueryResponse, PriceSourcesResponse, PricesAtResponse, SymbolMap};
 use crate::rate_limit::RateLimiter;
 use crate::realtime::Broadcaster;
 use crate::qn_proxy::QuicknodeProxy;
@@ -35,21 +40,227 @@ pub fn admin_login(body: Json<serde_json::Value>) -> AppResult<Json<serde_json::
 
 #[get("/admin")]
 pub fn admin_page() -> RawHtml<&'static str> {
-	RawHtml(r#"<!doctype html><html><head><meta charset='utf-8'/><meta name='viewport' content='width=device-width,initial-scale=1'/><title>Zera Oracle Admin</title><style>body{font-family:sans-serif;max-width:900px;margin:24px auto;padding:0 12px}table{border-collapse:collapse;width:100%}td,th{border:1px solid #ddd;padding:8px}input,button{padding:8px;margin:4px}#login{margin-bottom:16px;border:1px solid #ccc;padding:12px;border-radius:8px}</style></head><body><h2>Zera Devnet Oracle — Admin</h2><div id='login'><input id='user' placeholder='user'/> <input id='pwd' placeholder='password' type='password'/> <button onclick='login()'>Login</button> <span id='status'></span></div><div><button onclick='loadPrices()'>Refresh</button> <button onclick='addPrice()'>Add/Upsert</button></div><table id='tbl'><thead><tr><th>mint</th><th>symbol</th><th>mantissa</th><th>scale</th><th>decimals</th><th>updated</th><th>by</th><th>actions</th></tr></thead><tbody></tbody></table><script>let token=localStorage.getItem('jwt')||'';function setStatus(t){document.getElementById('status').innerText=t;}async function login(){const user=document.getElementById('user').value;const password=document.getElementById('pwd').value;const r=await fetch('/api/v1/admin/login',{method:'POST',headers:{'Content-Type':'application/json'},body:JSON.stringify({user,password})});if(r.ok){const j=await r.json();token=j.token;localStorage.setItem('jwt',token);setStatus('ok');loadPrices();}else setStatus('login failed');}async function loadPrices(){const r=await fetch('/api/v1/prices');const rows=await r.json();const tb=document.querySelector('#tbl tbody');tb.innerHTML='';rows.forEach(p=>{const tr=document.createElement('tr');tr.innerHTML=`<td>${p.mint}</td><td>${p.symbol||''}</td><td>${p.usd_mantissa}</td><td>${p.usd_scale}</td><td>${p.decimals??''}</td><td>${p.updated_at}</td><td>${p.updated_by}</td><td><button onclick='edit("${p.mint}")'>Edit</button><button onclick='delp("${p.mint}")'>Delete</button></td>`;tb.appendChild(tr);});}
-function edit(m){const symbol=prompt('symbol (opt)');const usd_mantissa=prompt('usd_mantissa (string)');const usd_scale=parseInt(prompt('usd_scale (u32)')||'0');const decimals=prompt('decimals (opt)');const body={};if(symbol!==null&&symbol!=='')body.symbol=symbol;if(usd_mantissa)body.usd_mantissa=usd_mantissa;if(!isNaN(usd_scale))body.usd_scale=usd_scale;if(decimals)body.decimals=parseInt(decimals);fetch(`/api/v1/prices/${m}`,{method:'PATCH',headers:{'Content-Type':'application/json','Authorization':`Bearer ${token}`},body:JSON.stringify(body)}).then(()=>loadPrices());}
-function delp(m){fetch(`/api/v1/prices/${m}`,{method:'DELETE',headers:{'Authorization':`Bearer ${token}`}}).then(()=>loadPrices());}
+	RawHtml(r#"<!doctype html><html><head><meta charset='utf-8'/><meta name='viewport' content='width=device-width,initial-scale=1'/><title>Zera Oracle Admin</title><style>body{font-family:sans-serif;max-width:900px;margin:24px auto;padding:0 12px}table{border-collapse:collapse;width:100%}td,th{border:1px solid #ddd;padding:8px}input,button{padding:8px;margin:4px}#login{margin-bottom:16px;border:1px solid #ccc;padding:12px;border-radius:8px}</style></head><body><h2>Zera Devnet Oracle — Admin</h2><div id='login'><input id='user' placeholder='user'/> <input id='pwd' placeholder='password' type='password'/> <button onclick='login()'>Login</button> <span id='status'></span></div><div><button onclick='loadPrices()'>Refresh</button> <button onclick='addPrice()'>Add/Upsert</button></div><table id='tbl'><thead><tr><th>mint</th><th>symbol</th><th>mantissa</th><th>scale</th><th>conf</th><th>decimals</th><th>updated</th><th>by</th><th>actions</th></tr></thead><tbody></tbody></table><script>let token=localStorage.getItem('jwt')||'';function setStatus(t){document.getElementById('status').innerText=t;}async function login(){const user=document.getElementById('user').value;const password=document.getElementById('pwd').value;const r=await fetch('/api/v1/admin/login',{method:'POST',headers:{'Content-Type':'application/json'},body:JSON.stringify({user,password})});if(r.ok){const j=await r.json();token=j.token;localStorage.setItem('jwt',token);setStatus('ok');loadPrices();}else setStatus('login failed');}async function loadPrices(){const r=await fetch('/api/v1/prices');const rows=await r.json();const tb=document.querySelector('#tbl tbody');tb.innerHTML='';rows.forEach(p=>{const tr=document.createElement('tr');tr.innerHTML=`<td>${p.mint}</td><td>${p.symbol||''}</td><td>${p.usd_mantissa}</td><td>${p.usd_scale}</td><td>${p.conf_mantissa??''}</td><td>${p.decimals??''}</td><td>${p.updated_at}</td><td>${p.updated_by}</td><td><button onclick='edit("${p.mint}",${p.version})'>Edit</button><button onclick='delp("${p.mint}",${p.version})'>Delete</button></td>`;tb.appendChild(tr);});}
+function done(r){if(r.status===409){r.json().then(j=>alert(j.error+' — reloading'));}loadPrices();}
+function edit(m,v){const symbol=prompt('symbol (opt)');const usd_mantissa=prompt('usd_mantissa (string)');const usd_scale=parseInt(prompt('usd_scale (u32)')||'0');const decimals=prompt('decimals (opt)');const body={};if(symbol!==null&&symbol!=='')body.symbol=symbol;if(usd_mantissa)body.usd_mantissa=usd_mantissa;if(!isNaN(usd_scale))body.usd_scale=usd_scale;if(decimals)body.decimals=parseInt(decimals);fetch(`/api/v1/prices/${m}`,{method:'PATCH',headers:{'Content-Type':'application/json','Authorization':`Bearer ${token}`,'If-Match':`"${v}"`},body:JSON.stringify(body)}).then(done);}
+function delp(m,v){fetch(`/api/v1/prices/${m}`,{method:'DELETE',headers:{'Authorization':`Bearer ${token}`,'If-Match':`"${v}"`}}).then(done);}
 function addPrice(){const mint=prompt('mint');if(!mint)return;const symbol=prompt('symbol');const usd_mantissa=prompt('usd_mantissa');const usd_scale=parseInt(prompt('usd_scale')||'2');const decimals=parseInt(prompt('decimals')||'6');const body={mint,symbol,usd_mantissa,usd_scale,decimals};fetch('/api/v1/prices',{method:'POST',headers:{'Content-Type':'application/json','Authorization':`Bearer ${token}`},body:JSON.stringify(body)}).then(()=>loadPrices());}
 </script></body></html>"#)
 }
 
-#[get("/prices")]
-pub fn list_prices(db: &State<DbState>) -> AppResult<Json<Vec<Price>>> {
-	Ok(Json(db.list_prices()?))
+#[get("/prices?<mints>&<symbols>&<updated_since>&<require_fresh>")]
+pub fn list_prices(db: &State<DbState>, mints: Option<&str>, symbols: Option<&str>, updated_since: Option<&str>, require_fresh: Option<bool>) -> AppResult<Json<PriceListResponse>> {
+	let require_fresh = require_fresh.unwrap_or(false);
+	if mints.is_none() && symbols.is_none() && updated_since.is_none() {
+		let mut prices = db.list_prices()?;
+		apply_freshness(db, prices.iter_mut(), require_fresh)?;
+		return Ok(Json(PriceListResponse::All(prices)));
+	}
+	let split = |v: Option<&str>| -> Vec<String> {
+		v.map(|s| s.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect()).unwrap_or_default()
+	};
+	let since_ms = parse_ts_param("updated_since", updated_since)?;
+	let mut out = query_prices(db, &split(mints), &split(symbols), since_ms)?;
+	apply_freshness(db, out.prices.values_mut(), require_fresh)?;
+	Ok(Json(PriceListResponse::Query(out)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceQueryBody {
+	#[serde(default)]
+	mints: Vec<String>,
+	#[serde(default)]
+	symbols: Vec<String>,
+	#[serde(default)]
+	updated_since: Option<String>,
+	#[serde(default)]
+	require_fresh: bool,
+}
+
+#[post("/prices/query", data = "<body>")]
+pub fn query_prices_post(db: &State<DbState>, body: Json<PriceQueryBody>) -> AppResult<Json<PriceQueryResponse>> {
+	let since_ms = parse_ts_param("updated_since", body.updated_since.as_deref())?;
+	let mut out = query_prices(db, &body.mints, &body.symbols, since_ms)?;
+	apply_freshness(db, out.prices.values_mut(), body.require_fresh)?;
+	Ok(Json(out))
+}
+
+/// Sets `age_secs`/`stale` on each price from the configured max ages. With `require_fresh`, any stale
+/// price turns the whole read into a 503 listing the offending mints.
+pub fn apply_freshness<'a>(db: &DbState, prices: impl IntoIterator<Item = &'a mut Price>, require_fresh: bool) -> AppResult<()> {
+	let cfg = db.get_config()?;
+	let now = now_ms();
+	let mut stale = Vec::new();
+	for p in prices {
+		p.set_freshness(cfg.max_age_for(&p.mint), now);
+		if p.stale == Some(true) { stale.push(p.mint.clone()); }
+	}
+	if require_fresh && !stale.is_empty() { return Err(AppError::Stale(stale)); }
+	Ok(())
+}
+
+/// Selects prices by mint (or `symbol:<SYMBOL>` key) and symbol, keyed by mint. With no mints or symbols
+/// every price is a candidate.
+fn query_prices(db: &DbState, mints: &[String], symbols: &[String], since_ms: Option<i64>) -> AppResult<PriceQueryResponse> {
+	if mints.len() + symbols.len() > 500 { return Err(AppError::BadRequest("at most 500 mints and symbols per query".into())); }
+	let all: BTreeMap<String, Price> = db.list_prices()?.into_iter().map(|p| (p.mint.clone(), p)).collect();
+	let mut out = PriceQueryResponse { prices: BTreeMap::new(), missing: Vec::new(), not_modified: Vec::new() };
+	let mut wanted: Vec<String> = Vec::new();
+	if mints.is_empty() && symbols.is_empty() {
+		wanted.extend(all.keys().cloned());
+	}
+	for key in mints {
+		match db.resolve_mint(key) {
+			Ok(mint) => wanted.push(mint),
+			Err(AppError::NoPrice(_)) => out.missing.push(key.clone()),
+			Err(e) => return Err(e),
+		}
+	}
+	for symbol in symbols {
+		match db.mint_for_symbol(symbol) {
+			Ok(mint) => wanted.push(mint),
+			Err(AppError::NoPrice(_)) => out.missing.push(format!("symbol:{}", symbol)),
+			Err(e) => return Err(e),
+		}
+	}
+	let explicit = !(mints.is_empty() && symbols.is_empty());
+	for mint in wanted {
+		if out.prices.contains_key(&mint) { continue; }
+		match all.get(&mint) {
+			Some(p) if since_ms.map(|s| p.updated_at_ms() >= s).unwrap_or(true) => { out.prices.insert(mint, p.clone()); }
+			Some(_) => { if explicit { out.not_modified.push(mint); } }
+			None => out.missing.push(mint),
+		}
+	}
+	Ok(out)
+}
+
+/// A single price with its version as a strong `ETag`.
+#[derive(Responder)]
+pub struct TaggedPrice {
+	inner: Json<Price>,
+	etag: Header<'static>,
+}
+
+impl From<Price> for TaggedPrice {
+	fn from(price: Price) -> Self {
+		let etag = Header::new("ETag", format!("\"{}\"", price.version));
+		Self { inner: Json(price), etag }
+	}
+}
+
+/// Parsed `If-Match` header: `None` when absent or `*`, otherwise the expected price version.
+/// Take it as `Result<IfMatch, AppError>` so a malformed header gets the usual JSON error body.
+pub struct IfMatch(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+	type Error = AppError;
+	async fn from_request(req: &'r Request<'_>) -> RequestOutcome<Self, Self::Error> {
+		let Some(raw) = req.headers().get_one("If-Match").map(str::trim) else { return Outcome::Success(IfMatch(None)) };
+		if raw == "*" { return Outcome::Success(IfMatch(None)); }
+		// If-Match uses strong comparison (RFC 9110 13.1.1), so a weak tag can never match
+		if raw.starts_with("W/") {
+			return Outcome::Error((Status::PreconditionFailed, AppError::PreconditionFailed("If-Match needs a strong ETag, not a weak one".into())));
+		}
+		let tag = raw.trim_matches('"');
+		match tag.parse::<u64>() {
+			Ok(v) => Outcome::Success(IfMatch(Some(v))),
+			Err(_) => Outcome::Error((Status::BadRequest, AppError::BadRequest("If-Match must be a single price version ETag".into()))),
+		}
+	}
+}
+
+#[get("/prices/<mint>?<require_fresh>")]
+pub fn get_price(mint: &str, db: &State<DbState>, require_fresh: Option<bool>) -> AppResult<TaggedPrice> {
+	let mut price = db.get_price(&db.resolve_mint(mint)?)?;
+	apply_freshness(db, [&mut price], require_fresh.unwrap_or(false))?;
+	Ok(price.into())
+}
+
+// ranked explicitly: otherwise it ties with `/prices/<mint>/history` and friends
+#[get("/prices/by-symbol/<symbol>?<require_fresh>", rank = 1)]
+pub fn get_price_by_symbol(symbol: &str, db: &State<DbState>, require_fresh: Option<bool>) -> AppResult<TaggedPrice> {
+	let mut price = db.get_price(&db.mint_for_symbol(symbol)?)?;
+	apply_freshness(db, [&mut price], require_fresh.unwrap_or(false))?;
+	Ok(price.into())
+}
+
+#[get("/prices/<mint>/history?<from>&<to>&<limit>&<cursor>")]
+pub fn get_price_history(db: &State<DbState>, mint: &str, from: Option<String>, to: Option<String>, limit: Option<usize>, cursor: Option<String>) -> AppResult<Json<PaginatedPriceHistoryResponse>> {
+	let limit = limit.unwrap_or(100).min(1000);
+	let from_ms = parse_ts_param("from", from.as_deref())?;
+	let to_ms = parse_ts_param("to", to.as_deref())?;
+	let cursor = match cursor {
+		Some(c) => Some(c.parse::<i64>().map_err(|_| AppError::BadRequest("invalid cursor".into()))?),
+		None => None,
+	};
+	let mint = db.resolve_mint(mint)?;
+	let (entries, next) = db.list_price_history(&mint, from_ms, to_ms, limit, cursor)?;
+	Ok(Json(PaginatedPriceHistoryResponse { entries, next_cursor: next }))
+}
+
+#[get("/prices/<mint>/at?<ts>")]
+pub fn get_price_at(db: &State<DbState>, mint: &str, ts: &str) -> AppResult<Json<Price>> {
+	let ts_ms = parse_ts_param("ts", Some(ts))?.unwrap_or_default();
+	db.get_price_at(&db.resolve_mint(mint)?, ts_ms)?
+		.map(Json)
+		.ok_or_else(|| AppError::NoPrice(format!("{} had no price at or before {}", mint, ts)))
+}
+
+/// Source readings behind the latest pegger write of the mint, or the latest at or before `version`.
+#[get("/prices/<mint>/sources?<version>")]
+pub fn get_price_sources(db: &State<DbState>, mint: &str, version: Option<u64>) -> AppResult<Json<PriceSourcesResponse>> {
+	let mint = db.resolve_mint(mint)?;
+	let (price_version, readings) = db.list_source_readings(&mint, version)?;
+	let policy = db.get_config()?.aggregation_for(&mint);
+	Ok(Json(PriceSourcesResponse { mint, price_version, policy, readings }))
 }
 
-#[get("/prices/<mint>")]
-pub fn get_price(mint: &str, db: &State<DbState>) -> AppResult<Json<Price>> {
-	Ok(Json(db.get_price(mint)?))
+#[get("/prices/at?<ts>&<mints>")]
+pub fn get_prices_at(db: &State<DbState>, ts: &str, mints: &str) -> AppResult<Json<PricesAtResponse>> {
+	let ts_ms = parse_ts_param("ts", Some(ts))?.unwrap_or_default();
+	let mints: Vec<&str> = mints.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+	if mints.is_empty() { return Err(AppError::BadRequest("mints required".into())); }
+	let mut out = PricesAtResponse { ts: ts.to_string(), prices: Default::default(), missing: Vec::new() };
+	for mint in mints {
+		let resolved = match db.resolve_mint(mint) {
+			Ok(m) => m,
+			Err(AppError::NoPrice(_)) => { out.missing.push(mint.to_string()); continue; }
+			Err(e) => return Err(e),
+		};
+		match db.get_price_at(&resolved, ts_ms)? {
+			Some(p) => { out.prices.insert(mint.to_string(), p); }
+			None => out.missing.push(mint.to_string()),
+		}
+	}
+	Ok(Json(out))
+}
+
+/// History rows a candle request may aggregate; wider ranges are refused rather than cut short.
+const MAX_CANDLE_POINTS: usize = 100_000;
+
+#[get("/prices/<mint>/candles?<interval>&<from>&<to>")]
+pub fn get_price_candles(db: &State<DbState>, mint: &str, interval: &str, from: Option<String>, to: Option<String>) -> AppResult<Json<Vec<Candle>>> {
+	let interval = Interval::parse(interval)?;
+	let to_ms = parse_ts_param("to", to.as_deref())?.unwrap_or_else(now_ms);
+	let from_ms = parse_ts_param("from", from.as_deref())?.unwrap_or(to_ms - interval.millis() * 500);
+	if from_ms > to_ms { return Err(AppError::BadRequest("from must not be after to".into())); }
+	if (to_ms - from_ms) / interval.millis() > 10_000 { return Err(AppError::BadRequest("range too large for interval (max 10000 buckets)".into())); }
+	let points = db.price_history_points(&db.resolve_mint(mint)?, from_ms, to_ms, MAX_CANDLE_POINTS + 1)?;
+	if points.len() > MAX_CANDLE_POINTS {
+		return Err(AppError::Unprocessable(format!("more than {} updates in range; narrow from/to", MAX_CANDLE_POINTS)));
+	}
+	Ok(Json(build_candles(&points, interval)))
+}
+
+fn parse_ts_param(name: &str, value: Option<&str>) -> AppResult<Option<i64>> {
+	match value {
+		Some(v) => parse_rfc3339_ms(v)
+			.map(Some)
+			.ok_or_else(|| AppError::BadRequest(format!("{} must be an RFC3339 timestamp", name))),
+		None => Ok(None),
+	}
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,55 +271,132 @@ struct UpsertPriceBody {
 	usd_mantissa: String,
 	usd_scale: u32,
 	#[serde(default)]
+	conf_mantissa: Option<String>,
+	#[serde(default)]
 	decimals: Option<u8>,
 }
 
-#[post("/prices", data = "<body>")]
+impl UpsertPriceBody {
+	/// `mint` may be a `symbol:<SYMBOL>` key for an already-mapped symbol; it is resolved here so the
+	/// literal key is never stored as a mint.
+	fn to_price(&self, db: &DbState, updated_at: &str, updated_by: &str) -> AppResult<Price> {
+		let mint = match db.resolve_mint(&self.mint) {
+			Err(AppError::NoPrice(msg)) => return Err(AppError::BadRequest(msg)),
+			other => other?,
+		};
+		Ok(Price {
+			mint,
+			symbol: self.symbol.clone(),
+			usd_mantissa: self.usd_mantissa.clone(),
+			usd_scale: self.usd_scale,
+			conf_mantissa: self.conf_mantissa.clone(),
+			updated_at: updated_at.to_string(),
+			updated_by: updated_by.to_string(),
+			decimals: self.decimals,
+			version: 0,
+			age_secs: None,
+			stale: None,
+		})
+	}
+}
+
+#[post("/prices?<force>", data = "<body>")]
 pub fn upsert_price(
 	user: AuthUser,
 	db: &State<DbState>,
 	bc: &State<Broadcaster>,
 	limiter: &State<RateLimiter>,
+	force: Option<bool>,
 	body: Json<UpsertPriceBody>,
 ) -> AppResult<(Status, Json<Price>)> {
 	user.require_admin()?;
 	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
-	let now = Price::now_iso();
-	let price = Price {
-		mint: body.mint.clone(),
-		symbol: body.symbol.clone(),
-		usd_mantissa: body.usd_mantissa.clone(),
-		usd_scale: body.usd_scale,
-		updated_at: now,
-		updated_by: format!("admin:{}", user.subject),
-		decimals: body.decimals,
-	};
-	let saved = db.upsert_price(&price, &user.subject)?;
+	let price = body.to_price(db, &Price::now_iso(), &format!("admin:{}", user.subject))?;
+	let saved = db.upsert_price(&price, &user.subject, force.unwrap_or(false)).inspect_err(|e| alert_on_breaker(bc, e))?;
 	bc.publish(json!({"type":"price_upsert","price": saved}));
 	Ok((Status::Created, Json(saved)))
 }
 
-#[patch("/prices/<mint>", data = "<patch>")]
-pub fn patch_price(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str, patch: Json<serde_json::Value>) -> AppResult<Json<Price>> {
+#[derive(Debug, Deserialize)]
+pub struct UpsertPriceBatchBody {
+	prices: Vec<UpsertPriceBody>,
+}
+
+/// Validates every entry up front, then writes them all in one transaction. Counts as a single write against the rate limit.
+#[post("/prices/batch?<force>", data = "<body>")]
+pub fn upsert_price_batch(
+	user: AuthUser,
+	db: &State<DbState>,
+	bc: &State<Broadcaster>,
+	limiter: &State<RateLimiter>,
+	force: Option<bool>,
+	body: Json<UpsertPriceBatchBody>,
+) -> AppResult<(Status, Json<serde_json::Value>)> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	if body.prices.is_empty() { return Err(AppError::BadRequest("prices must not be empty".into())); }
+	if body.prices.len() > 500 { return Err(AppError::BadRequest("at most 500 prices per batch".into())); }
+	let now = Price::now_iso();
+	let updated_by = format!("admin:{}", user.subject);
+	let mut seen = std::collections::HashSet::new();
+	let mut prices = Vec::with_capacity(body.prices.len());
+	for (i, entry) in body.prices.iter().enumerate() {
+		let invalid = |msg: String| AppError::BadRequest(format!("prices[{}] ({}): {}", i, entry.mint, msg));
+		if entry.mint.trim().is_empty() { return Err(invalid("mint must not be empty".into())); }
+		let as_invalid = |e| match e {
+			AppError::BadRequest(msg) => invalid(msg),
+			other => other,
+		};
+		let price = entry.to_price(db, &now, &updated_by).map_err(as_invalid)?;
+		if !seen.insert(price.mint.clone()) { return Err(invalid("duplicate mint in batch".into())); }
+		price.usd_amount().map_err(as_invalid)?;
+		prices.push(price);
+	}
+	let (batch_id, saved) = db.upsert_prices_batch(&prices, &user.subject, force.unwrap_or(false)).inspect_err(|e| alert_on_breaker(bc, e))?;
+	bc.publish(json!({"type":"price_batch","batch_id": batch_id, "prices": saved}));
+	Ok((Status::Created, Json(json!({"batch_id": batch_id, "prices": saved}))))
+}
+
+#[patch("/prices/<mint>?<force>", data = "<patch>")]
+#[allow(clippy::too_many_arguments)]
+pub fn patch_price(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str, force: Option<bool>, if_match: Result<IfMatch, AppError>, patch: Json<serde_json::Value>) -> AppResult<TaggedPrice> {
 	user.require_admin()?;
 	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
-	let updated = db.patch_price(mint, patch.into_inner(), &user.subject)?;
+	let expected = if_match?.0;
+	let updated = db.patch_price(mint, patch.into_inner(), &user.subject, expected, force.unwrap_or(false)).inspect_err(|e| alert_on_breaker(bc, e))?;
 	bc.publish(json!({"type":"price_patch","mint": mint, "price": updated}));
-	Ok(Json(updated))
+	Ok(updated.into())
 }
 
-#[delete("/prices/<mint>")]
-pub fn delete_price(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str) -> AppResult<Status> {
+#[delete("/prices/<mint>?<force>")]
+pub fn delete_price(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, mint: &str, force: Option<bool>, if_match: Result<IfMatch, AppError>) -> AppResult<Status> {
 	user.require_admin()?;
 	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
-	db.delete_price(mint, &user.subject)?;
+	db.delete_price(mint, &user.subject, if_match?.0, force.unwrap_or(false))?;
 	bc.publish(json!({"type":"price_delete","mint": mint}));
 	Ok(Status::NoContent)
 }
 
-#[get("/symbols")]
-pub fn get_symbols(db: &State<DbState>) -> AppResult<Json<Vec<SymbolMap>>> {
-	Ok(Json(db.list_symbols()?))
+#[get("/symbols?<mint>&<q>")]
+pub fn get_symbols(db: &State<DbState>, mint: Option<&str>, q: Option<&str>) -> AppResult<Json<Vec<SymbolMap>>> {
+	let q = q.map(|v| v.to_ascii_lowercase());
+	let symbols = db
+		.list_symbols()?
+		.into_iter()
+		.filter(|s| mint.map(|m| s.mint == m).unwrap_or(true))
+		.filter(|s| q.as_deref().map(|q| s.symbol.to_ascii_lowercase().contains(q)).unwrap_or(true))
+		.collect();
+	Ok(Json(symbols))
+}
+
+#[get("/symbols/<symbol>")]
+pub fn get_symbol(db: &State<DbState>, symbol: &str) -> AppResult<Json<SymbolMap>> {
+	Ok(Json(db.get_symbol(symbol)?))
+}
+
+#[get("/mints/<mint>/symbols")]
+pub fn get_mint_symbols(db: &State<DbState>, mint: &str) -> AppResult<Json<Vec<SymbolMap>>> {
+	Ok(Json(db.symbols_for_mint(mint)?))
 }
 
 #[derive(Debug, Deserialize)]
@@ -118,11 +406,20 @@ struct UpsertSymbolBody { symbol: String, mint: String }
 pub fn upsert_symbol(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, body: Json<UpsertSymbolBody>) -> AppResult<Status> {
 	user.require_admin()?;
 	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
-	db.upsert_symbol(&body.symbol, &body.mint)?;
-	bc.publish(json!({"type":"symbol_upsert","symbol": body.symbol, "mint": body.mint}));
+	let saved = db.upsert_symbol(&body.symbol, &body.mint, &user.subject)?;
+	bc.publish(json!({"type":"symbol_upsert","symbol": saved.symbol, "mint": saved.mint}));
 	Ok(Status::Created)
 }
 
+#[delete("/symbols/<symbol>")]
+pub fn delete_symbol(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, symbol: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = db.delete_symbol(symbol, &user.subject)?;
+	bc.publish(json!({"type":"symbol_delete","symbol": removed.symbol, "mint": removed.mint}));
+	Ok(Status::NoContent)
+}
+
 #[get("/config")]
 pub fn get_config(db: &State<DbState>) -> AppResult<Json<Config>> {
 	Ok(Json(db.get_config()?))
@@ -161,18 +458,57 @@ pub fn mount_routes() -> Vec<Route> {
 		admin_page,
 		// prices
 		list_prices,
+		query_prices_post,
 		get_price,
+		get_price_by_symbol,
+		get_price_history,
+		get_price_at,
+		get_price_sources,
+		get_prices_at,
+		get_price_candles,
 		upsert_price,
+		upsert_price_batch,
 		patch_price,
 		delete_price,
+		crate::freeze::freeze_mint,
+		crate::freeze::unfreeze_mint,
+		crate::freeze::list_frozen,
 		// symbols
 		get_symbols,
+		get_symbol,
+		get_mint_symbols,
 		upsert_symbol,
+		delete_symbol,
 		// config
 		get_config,
 		patch_config,
 		// audit
 		get_audit,
+		// quotes
+		crate::quote::quote_value,
+		crate::quote::quote_value_batch,
+		crate::quote::quote_convert,
+		// schedules
+		crate::scheduler::create_schedule,
+		crate::scheduler::list_schedules,
+		crate::scheduler::delete_schedule,
+		// scenarios
+		crate::scenarios::start_scenario,
+		crate::scenarios::list_scenarios,
+		crate::scenarios::get_scenario,
+		crate::scenarios::delete_scenario,
+		// replays
+		crate::replay::start_replay,
+		crate::replay::list_replays,
+		crate::replay::get_replay,
+		crate::replay::delete_replay,
+		// pegs
+		crate::pegger::create_peg,
+		crate::pegger::list_pegs,
+		crate::pegger::peg_status,
+		crate::pegger::get_peg,
+		crate::pegger::update_peg,
+		crate::pegger::delete_peg,
 		// examples
 		examples,
 		// realtime
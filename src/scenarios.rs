@@ -0,0 +1,405 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::freeze::alert_on_breaker;
+use crate::models::{div_round, Price, Rounding, UsdAmount};
+use crate::rate_limit::RateLimiter;
+use crate::realtime::Broadcaster;
+
+const MAX_RUNNING: usize = 32;
+
+/// Price generator of a scenario. Every generator is a pure function of its parameters, the start price
+/// and the seed, so re-running a scenario with the same body reproduces the same prices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScenarioSpec {
+	/// Geometric Brownian motion; `drift` and `volatility` are per step, e.g. `0.01` = 1%.
+	RandomWalk {
+		#[serde(default)]
+		drift: f64,
+		volatility: f64,
+	},
+	/// Straight line from the start price to `to` over `steps`, ending on `to`.
+	Ramp { to: String },
+	/// Holds the start price, then drops by `drop_bps` at `at_step` and stays there.
+	Crash {
+		drop_bps: u32,
+		#[serde(default)]
+		at_step: u64,
+	},
+	/// Oscillates around the start price by `amplitude_bps` with a period of `period_steps`.
+	Sine { amplitude_bps: u32, period_steps: u64 },
+	/// One decimal price per line (last column if comma-separated); non-numeric lines such as headers are skipped.
+	Csv { csv: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartScenarioBody {
+	mint: String,
+	#[serde(flatten)]
+	spec: ScenarioSpec,
+	/// Decimal start price; defaults to the mint's current price
+	#[serde(default)]
+	start: Option<String>,
+	/// Output scale; defaults to the start price's scale, at least 6
+	#[serde(default)]
+	scale: Option<u32>,
+	#[serde(default)]
+	seed: Option<u64>,
+	#[serde(default)]
+	interval_ms: Option<u64>,
+	/// Number of updates; unlimited when omitted (required for `ramp`)
+	#[serde(default)]
+	steps: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioInfo {
+	pub id: String,
+	pub mint: String,
+	#[serde(flatten)]
+	pub spec: ScenarioSpec,
+	pub start: UsdAmount,
+	pub scale: u32,
+	pub seed: u64,
+	pub interval_ms: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub steps: Option<u64>,
+	/// `running`, `completed`, `failed` or `stopped`
+	pub status: String,
+	/// Updates written so far
+	pub step: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_price: Option<UsdAmount>,
+	pub started_at: String,
+	pub started_by: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl ScenarioInfo {
+	pub fn actor(&self) -> String {
+		format!("scenario:{}", self.id)
+	}
+}
+
+struct ScenarioRun {
+	info: ScenarioInfo,
+	task: Option<JoinHandle<()>>,
+}
+
+/// Running and finished scenarios, kept in memory until deleted.
+#[derive(Clone, Default)]
+pub struct ScenarioEngine {
+	runs: Arc<DashMap<String, ScenarioRun>>,
+}
+
+impl ScenarioEngine {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn list(&self) -> Vec<ScenarioInfo> {
+		let mut out: Vec<ScenarioInfo> = self.runs.iter().map(|r| r.info.clone()).collect();
+		out.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+		out
+	}
+
+	pub fn get(&self, id: &str) -> Option<ScenarioInfo> {
+		self.runs.get(id).map(|r| r.info.clone())
+	}
+
+	pub fn start(&self, info: ScenarioInfo, db: DbState, bc: Broadcaster) -> AppResult<ScenarioInfo> {
+		let running = self.runs.iter().filter(|r| r.info.status == "running").count();
+		if running >= MAX_RUNNING { return Err(AppError::Conflict(format!("at most {} scenarios may run at once", MAX_RUNNING))); }
+		let mut generator = Generator::new(&info)?;
+		let id = info.id.clone();
+		self.runs.insert(id.clone(), ScenarioRun { info: info.clone(), task: None });
+		let this = self.clone();
+		let task = tokio::spawn(async move {
+			let mut interval = tokio::time::interval(Duration::from_millis(info.interval_ms));
+			let actor = info.actor();
+			let mut step = 0u64;
+			let outcome = loop {
+				if info.steps.map(|n| step >= n).unwrap_or(false) { break Ok(()); }
+				interval.tick().await;
+				let Some(amount) = generator.value(step) else { break Ok(()) };
+				let amount = match amount { Ok(a) => a, Err(e) => break Err(e) };
+				let current = match db.get_price(&info.mint) {
+					Ok(p) => Some(p),
+					Err(AppError::NotFound) => None,
+					Err(e) => break Err(e),
+				};
+				let price = Price::next_from(current.as_ref(), &info.mint, amount, &actor);
+				match db.upsert_price(&price, &actor, false) {
+					Ok(saved) => bc.publish(json!({"type":"price_upsert","price": saved})),
+					Err(e) => {
+						alert_on_breaker(&bc, &e);
+						break Err(e);
+					}
+				}
+				step += 1;
+				if let Some(mut run) = this.runs.get_mut(&info.id) {
+					run.info.step = step;
+					run.info.last_price = Some(amount);
+				}
+			};
+			if let Some(mut run) = this.runs.get_mut(&info.id) {
+				match outcome {
+					Ok(()) => run.info.status = "completed".into(),
+					Err(e) => {
+						run.info.status = "failed".into();
+						run.info.error = Some(e.to_string());
+					}
+				}
+				bc.publish(json!({"type":"scenario_end","scenario": run.info}));
+			}
+		});
+		if let Some(mut run) = self.runs.get_mut(&id) { run.task = Some(task); }
+		self.get(&id).ok_or(AppError::NotFound)
+	}
+
+	/// Aborts the scenario if it is still running and forgets it.
+	pub fn remove(&self, id: &str) -> AppResult<ScenarioInfo> {
+		let (_, run) = self.runs.remove(id).ok_or(AppError::NotFound)?;
+		let mut info = run.info;
+		if let Some(task) = run.task {
+			if !task.is_finished() {
+				task.abort();
+				info.status = "stopped".into();
+			}
+		}
+		Ok(info)
+	}
+}
+
+/// Stateful side of a scenario; `value(step)` must be called with consecutive steps starting at 0.
+struct Generator {
+	spec: ScenarioSpec,
+	start: UsdAmount,
+	scale: u32,
+	steps: Option<u64>,
+	rng: StdRng,
+	walk: f64,
+	ramp_to: Option<UsdAmount>,
+	csv: Vec<UsdAmount>,
+}
+
+impl Generator {
+	fn new(info: &ScenarioInfo) -> AppResult<Self> {
+		let start = info.start.rescale(info.scale, Rounding::HalfEven)?;
+		let mut g = Generator {
+			spec: info.spec.clone(),
+			start,
+			scale: info.scale,
+			steps: info.steps,
+			rng: StdRng::seed_from_u64(info.seed),
+			walk: to_f64(&start),
+			ramp_to: None,
+			csv: Vec::new(),
+		};
+		match &info.spec {
+			ScenarioSpec::RandomWalk { volatility, drift } => {
+				if !volatility.is_finite() || *volatility < 0.0 || !drift.is_finite() {
+					return Err(AppError::BadRequest("random_walk needs a finite drift and a non-negative volatility".into()));
+				}
+			}
+			ScenarioSpec::Ramp { to } => {
+				if info.steps.unwrap_or(0) == 0 { return Err(AppError::BadRequest("ramp requires steps > 0".into())); }
+				g.ramp_to = Some(UsdAmount::from_decimal_str(to)?.rescale(info.scale, Rounding::HalfEven)?);
+			}
+			ScenarioSpec::Crash { drop_bps, .. } => {
+				if *drop_bps > 10_000 { return Err(AppError::BadRequest("drop_bps must be <= 10000".into())); }
+			}
+			ScenarioSpec::Sine { amplitude_bps, period_steps } => {
+				if *amplitude_bps > 10_000 { return Err(AppError::BadRequest("amplitude_bps must be <= 10000".into())); }
+				if *period_steps == 0 { return Err(AppError::BadRequest("period_steps must be > 0".into())); }
+			}
+			ScenarioSpec::Csv { csv } => {
+				for line in csv.lines() {
+					let field = line.rsplit(',').next().unwrap_or("").trim();
+					if let Ok(v) = UsdAmount::from_decimal_str(field) {
+						g.csv.push(v.rescale(info.scale, Rounding::HalfEven)?);
+					}
+				}
+				if g.csv.is_empty() { return Err(AppError::BadRequest("csv has no prices".into())); }
+			}
+		}
+		Ok(g)
+	}
+
+	/// Price for `step`, or `None` once a finite generator is exhausted.
+	fn value(&mut self, step: u64) -> Option<AppResult<UsdAmount>> {
+		let start = self.start.mantissa();
+		let out = match &self.spec {
+			ScenarioSpec::RandomWalk { drift, volatility } => {
+				if step > 0 {
+					// Box-Muller; 1 - u keeps ln() away from zero
+					let (u1, u2): (f64, f64) = (1.0 - self.rng.gen::<f64>(), self.rng.gen());
+					let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+					self.walk *= ((drift - volatility * volatility / 2.0) + volatility * z).exp();
+				}
+				from_f64(self.walk, self.scale)
+			}
+			ScenarioSpec::Ramp { .. } => {
+				let to = self.ramp_to.map(|t| t.mantissa()).unwrap_or(start) as i128;
+				// the last of `steps` lands on `to`; a one-step ramp is just `to`
+				let n = self.steps.unwrap_or(1).saturating_sub(1) as i128;
+				let k = (step as i128).min(n);
+				let mantissa = if n == 0 { to } else { start as i128 + (to - start as i128) * k / n };
+				UsdAmount::new(mantissa as u128, self.scale)
+			}
+			ScenarioSpec::Crash { drop_bps, at_step } => {
+				if step < *at_step {
+					Ok(self.start)
+				} else {
+					match start.checked_mul(10_000 - *drop_bps as u128) {
+						Some(num) => UsdAmount::new(div_round(num, 10_000, Rounding::HalfEven), self.scale),
+						None => Err(AppError::BadRequest("start price too large for crash".into())),
+					}
+				}
+			}
+			ScenarioSpec::Sine { amplitude_bps, period_steps } => {
+				let phase = 2.0 * std::f64::consts::PI * (step % period_steps) as f64 / *period_steps as f64;
+				from_f64(to_f64(&self.start) * (1.0 + *amplitude_bps as f64 / 10_000.0 * phase.sin()), self.scale)
+			}
+			ScenarioSpec::Csv { .. } => return self.csv.get(step as usize).copied().map(Ok),
+		};
+		Some(out)
+	}
+}
+
+fn to_f64(amount: &UsdAmount) -> f64 {
+	amount.to_decimal_string().parse().unwrap_or(0.0)
+}
+
+fn from_f64(v: f64, scale: u32) -> AppResult<UsdAmount> {
+	if !v.is_finite() || v < 0.0 { return Err(AppError::BadRequest(format!("generator produced an invalid price {}", v))); }
+	UsdAmount::from_decimal_str(&format!("{:.*}", scale as usize, v))?.rescale(scale, Rounding::HalfEven)
+}
+
+#[post("/scenarios", data = "<body>")]
+pub fn start_scenario(
+	user: AuthUser,
+	db: &State<DbState>,
+	bc: &State<Broadcaster>,
+	limiter: &State<RateLimiter>,
+	engine: &State<ScenarioEngine>,
+	body: Json<StartScenarioBody>,
+) -> AppResult<(Status, Json<ScenarioInfo>)> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let body = body.into_inner();
+	let mint = db.resolve_mint(&body.mint)?;
+	let start = match &body.start {
+		Some(s) => UsdAmount::from_decimal_str(s)?,
+		None => db.get_price(&mint).map_err(|_| AppError::BadRequest(format!("{} has no price; pass start", mint)))?.usd_amount()?,
+	};
+	let info = ScenarioInfo {
+		id: Uuid::new_v4().to_string(),
+		mint,
+		spec: body.spec,
+		start,
+		scale: body.scale.unwrap_or(start.scale().max(6)),
+		seed: body.seed.unwrap_or_else(rand::random),
+		interval_ms: body.interval_ms.unwrap_or(1000).max(100),
+		steps: body.steps,
+		status: "running".into(),
+		step: 0,
+		last_price: None,
+		started_at: Price::now_iso(),
+		started_by: user.subject.clone(),
+		error: None,
+	};
+	let started = engine.start(info, db.inner().clone(), bc.inner().clone())?;
+	db.insert_audit("START_SCENARIO", &user.subject, &started.mint, None, Some(serde_json::to_value(&started)?))?;
+	bc.publish(json!({"type":"scenario_start","scenario": started}));
+	Ok((Status::Created, Json(started)))
+}
+
+#[get("/scenarios")]
+pub fn list_scenarios(engine: &State<ScenarioEngine>) -> Json<Vec<ScenarioInfo>> {
+	Json(engine.list())
+}
+
+#[get("/scenarios/<id>")]
+pub fn get_scenario(engine: &State<ScenarioEngine>, id: &str) -> AppResult<Json<ScenarioInfo>> {
+	engine.get(id).map(Json).ok_or(AppError::NotFound)
+}
+
+#[delete("/scenarios/<id>")]
+pub fn delete_scenario(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, engine: &State<ScenarioEngine>, id: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = engine.remove(id)?;
+	db.insert_audit("STOP_SCENARIO", &user.subject, &removed.mint, Some(serde_json::to_value(&removed)?), None)?;
+	bc.publish(json!({"type":"scenario_stop","scenario": removed}));
+	Ok(Status::NoContent)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ramp(from: &str, to: &str, steps: u64) -> Generator {
+		let scale = 6;
+		Generator {
+			spec: ScenarioSpec::Ramp { to: to.into() },
+			start: UsdAmount::from_decimal_str(from).unwrap().rescale(scale, Rounding::HalfEven).unwrap(),
+			scale,
+			steps: Some(steps),
+			rng: StdRng::seed_from_u64(0),
+			walk: 0.0,
+			ramp_to: Some(UsdAmount::from_decimal_str(to).unwrap().rescale(scale, Rounding::HalfEven).unwrap()),
+			csv: Vec::new(),
+		}
+	}
+
+	fn walk(seed: u64) -> Generator {
+		let scale = 6;
+		let start = UsdAmount::from_decimal_str("1").unwrap().rescale(scale, Rounding::HalfEven).unwrap();
+		Generator {
+			spec: ScenarioSpec::RandomWalk { drift: 0.0, volatility: 0.05 },
+			start,
+			scale,
+			steps: None,
+			rng: StdRng::seed_from_u64(seed),
+			walk: to_f64(&start),
+			ramp_to: None,
+			csv: Vec::new(),
+		}
+	}
+
+	fn values(g: &mut Generator, steps: u64) -> Vec<String> {
+		(0..steps).map(|k| g.value(k).unwrap().unwrap().to_string()).collect()
+	}
+
+	#[test]
+	fn ramp_ends_on_target() {
+		assert_eq!(values(&mut ramp("1", "2", 1), 1), ["2.000000"]);
+		assert_eq!(values(&mut ramp("1", "2", 2), 2), ["1.000000", "2.000000"]);
+		assert_eq!(values(&mut ramp("2", "1", 5), 5), ["2.000000", "1.750000", "1.500000", "1.250000", "1.000000"]);
+	}
+
+	#[test]
+	fn random_walk_is_reproducible_per_seed() {
+		let series = values(&mut walk(42), 50);
+		assert_eq!(series[0], "1.000000");
+		assert_eq!(series, values(&mut walk(42), 50));
+		assert_ne!(series, values(&mut walk(43), 50));
+	}
+}
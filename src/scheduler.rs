@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::db::DbState;
+use crate::errors::{AppError, AppResult};
+use crate::freeze::alert_on_breaker;
+use crate::models::{now_ms, parse_rfc3339_ms, Price, ScheduledPrice, UsdAmount};
+use crate::rate_limit::RateLimiter;
+use crate::realtime::Broadcaster;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleBody {
+	mint: String,
+	#[serde(default)]
+	symbol: Option<String>,
+	usd_mantissa: String,
+	usd_scale: u32,
+	#[serde(default)]
+	decimals: Option<u8>,
+	/// RFC3339; a time in the past applies on the next tick
+	run_at: String,
+}
+
+#[post("/schedules", data = "<body>")]
+pub fn create_schedule(
+	user: AuthUser,
+	db: &State<DbState>,
+	bc: &State<Broadcaster>,
+	limiter: &State<RateLimiter>,
+	body: Json<CreateScheduleBody>,
+) -> AppResult<(Status, Json<ScheduledPrice>)> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let amount = UsdAmount::parse(&body.usd_mantissa, body.usd_scale)?;
+	if parse_rfc3339_ms(&body.run_at).is_none() { return Err(AppError::BadRequest("run_at must be an RFC3339 timestamp".into())); }
+	let schedule = ScheduledPrice {
+		id: Uuid::new_v4().to_string(),
+		mint: db.resolve_mint(&body.mint)?,
+		symbol: body.symbol.clone(),
+		usd_mantissa: amount.mantissa().to_string(),
+		usd_scale: amount.scale(),
+		decimals: body.decimals,
+		run_at: body.run_at.clone(),
+		status: "pending".into(),
+		created_at: Price::now_iso(),
+		created_by: user.subject.clone(),
+		applied_at: None,
+		error: None,
+	};
+	let saved = db.insert_schedule(&schedule, &user.subject)?;
+	bc.publish(json!({"type":"schedule_create","schedule": saved}));
+	Ok((Status::Created, Json(saved)))
+}
+
+#[get("/schedules?<mint>&<status>")]
+pub fn list_schedules(user: AuthUser, db: &State<DbState>, mint: Option<&str>, status: Option<&str>) -> AppResult<Json<Vec<ScheduledPrice>>> {
+	user.require_admin()?;
+	let mint = mint.map(|m| db.resolve_mint(m)).transpose()?;
+	Ok(Json(db.list_schedules(mint.as_deref(), status)?))
+}
+
+#[delete("/schedules/<id>")]
+pub fn delete_schedule(user: AuthUser, db: &State<DbState>, bc: &State<Broadcaster>, limiter: &State<RateLimiter>, id: &str) -> AppResult<Status> {
+	user.require_admin()?;
+	if !limiter.check_and_increment(&user.subject) { return Err(AppError::TooManyRequests); }
+	let removed = db.delete_schedule(id, &user.subject)?;
+	bc.publish(json!({"type":"schedule_delete","schedule": removed}));
+	Ok(Status::NoContent)
+}
+
+/// Polls for due schedules and applies them as `scheduler`. Disabled with `SCHEDULER_ENABLED=0`.
+pub fn spawn_scheduler(db: DbState, bc: Broadcaster) {
+	if std::env::var("SCHEDULER_ENABLED").map(|v| v == "0" || v.eq_ignore_ascii_case("false")).unwrap_or(false) { return; }
+	let tick_ms = std::env::var("SCHEDULER_TICK_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(1000u64).max(50);
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(Duration::from_millis(tick_ms));
+		loop {
+			interval.tick().await;
+			let due = match db.due_schedules(now_ms()) {
+				Ok(due) => due,
+				Err(e) => { tracing::warn!("scheduler: failed to load due schedules: {}", e); continue; }
+			};
+			for schedule in due {
+				let result = apply_schedule(&db, &schedule);
+				match &result {
+					Ok(saved) => bc.publish(json!({"type":"price_upsert","price": saved})),
+					Err(e) => {
+						alert_on_breaker(&bc, e);
+						tracing::warn!("scheduler: schedule {} for {} failed: {}", schedule.id, schedule.mint, e);
+					}
+				}
+				let error = result.err().map(|e| e.to_string());
+				if let Err(e) = db.finish_schedule(&schedule.id, error.as_deref()) {
+					tracing::warn!("scheduler: could not mark schedule {}: {}", schedule.id, e);
+				}
+			}
+		}
+	});
+}
+
+/// Writes the scheduled price, keeping the current symbol and decimals where the schedule leaves them unset.
+fn apply_schedule(db: &DbState, schedule: &ScheduledPrice) -> AppResult<Price> {
+	let current = match db.get_price(&schedule.mint) {
+		Ok(p) => Some(p),
+		Err(AppError::NotFound) => None,
+		Err(e) => return Err(e),
+	};
+	let amount = UsdAmount::parse(&schedule.usd_mantissa, schedule.usd_scale)?;
+	let mut price = Price::next_from(current.as_ref(), &schedule.mint, amount, "scheduler");
+	if schedule.symbol.is_some() { price.symbol = schedule.symbol.clone(); }
+	if schedule.decimals.is_some() { price.decimals = schedule.decimals; }
+	db.upsert_price(&price, "scheduler", false)
+}
@@ -0,0 +1,560 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppResult};
+use crate::helius::HeliusPriceService;
+use crate::json_path::JsonPath;
+use crate::models::{Price, Rounding, UsdAmount, MAX_SCALE};
+use crate::pyth;
+use crate::qn_proxy::QuicknodeProxy;
+
+/// Somewhere a USD price for a mint can be read from. The pegger polls one source per registration
+/// and aggregates the readings of every source registered for the same mint.
+#[rocket::async_trait]
+pub trait PriceSource: Send + Sync {
+	/// Short label for logs, e.g. `http_json`
+	fn kind(&self) -> &'static str;
+	async fn fetch(&self, mint: &str) -> AppResult<UsdAmount>;
+
+	/// `fetch` plus the confidence interval, for sources that report one.
+	async fn fetch_quote(&self, mint: &str) -> AppResult<Quote> {
+		Ok(Quote { price: self.fetch(mint).await?, conf: None })
+	}
+}
+
+/// A fetched price and, when the source reports one, its confidence interval (`price ± conf`).
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+	pub price: UsdAmount,
+	pub conf: Option<UsdAmount>,
+}
+
+/// Polls an HTTP endpoint returning JSON and reads the number (or numeric string) at a [`JsonPath`].
+pub struct HttpJsonSource {
+	client: reqwest::Client,
+	url: String,
+	path: JsonPath,
+	headers: HeaderMap,
+}
+
+#[rocket::async_trait]
+impl PriceSource for HttpJsonSource {
+	fn kind(&self) -> &'static str { "http_json" }
+
+	async fn fetch(&self, _mint: &str) -> AppResult<UsdAmount> {
+		let resp = self.client.get(&self.url).headers(self.headers.clone()).send().await.map_err(|e| AppError::Anyhow(e.into()))?;
+		let status = resp.status();
+		if !status.is_success() { return Err(AppError::NoPrice(format!("{} returned {}", self.url, status))); }
+		let body = resp.text().await.map_err(|e| AppError::Anyhow(e.into()))?;
+		self.path.decimal_at(&body)
+	}
+}
+
+/// Helius DAS `getAsset` price, through the service's TTL cache.
+pub struct HeliusSource {
+	service: HeliusPriceService,
+}
+
+#[rocket::async_trait]
+impl PriceSource for HeliusSource {
+	fn kind(&self) -> &'static str { "helius" }
+
+	async fn fetch(&self, mint: &str) -> AppResult<UsdAmount> {
+		let usd = self.service.price_usd(mint).await?.ok_or_else(|| AppError::NoPrice(format!("helius has no price for {}", mint)))?;
+		UsdAmount::from_decimal_str(&usd.to_string())
+	}
+}
+
+/// `price_usd` of the QuickNode token endpoint, through the proxy's cache. `address` defaults to the mint.
+pub struct QuicknodeSource {
+	proxy: Arc<QuicknodeProxy>,
+	address: Option<String>,
+}
+
+#[rocket::async_trait]
+impl PriceSource for QuicknodeSource {
+	fn kind(&self) -> &'static str { "quicknode" }
+
+	async fn fetch(&self, mint: &str) -> AppResult<UsdAmount> {
+		let path = format!("addon/912/networks/solana/tokens/{}", self.address.as_deref().unwrap_or(mint));
+		let (status, body) = self.proxy.get_cached(None, &path, &[]).await?;
+		if status.code != 200 { return Err(AppError::NoPrice(format!("quicknode returned {} for {}", status.code, path))); }
+		JsonPath::parse("/data/attributes/price_usd")?
+			.decimal_at(&body)
+			.or_else(|_| JsonPath::parse("/price_usd")?.decimal_at(&body))
+			.map_err(|_| AppError::NoPrice(format!("quicknode has no price_usd for {}", path)))
+	}
+}
+
+/// Aggregate price of a Pyth price account, read with `getAccountInfo`. Prices that are not trading or
+/// were published more than `max_slot_lag` slots before the slot the RPC node answered at are rejected.
+pub struct PythSource {
+	client: reqwest::Client,
+	rpc_url: String,
+	account: String,
+	max_slot_lag: u64,
+}
+
+#[rocket::async_trait]
+impl PriceSource for PythSource {
+	fn kind(&self) -> &'static str { "pyth" }
+
+	async fn fetch(&self, mint: &str) -> AppResult<UsdAmount> {
+		Ok(self.fetch_quote(mint).await?.price)
+	}
+
+	async fn fetch_quote(&self, _mint: &str) -> AppResult<Quote> {
+		let (slot, price) = pyth::fetch_price_account(&self.client, &self.rpc_url, &self.account).await?;
+		let lag = slot.saturating_sub(price.publish_slot);
+		if lag > self.max_slot_lag {
+			return Err(AppError::NoPrice(format!("pyth price {} was published {} slots ago (max {})", self.account, lag, self.max_slot_lag)));
+		}
+		let (price, conf) = price.amounts()?;
+		Ok(Quote { price, conf: Some(conf) })
+	}
+}
+
+/// Always the same price; handy for pinning a devnet mint.
+pub struct StaticSource {
+	price: UsdAmount,
+}
+
+#[rocket::async_trait]
+impl PriceSource for StaticSource {
+	fn kind(&self) -> &'static str { "static" }
+
+	async fn fetch(&self, _mint: &str) -> AppResult<UsdAmount> {
+		Ok(self.price)
+	}
+}
+
+/// Stands in for header values in anything serialized out of the server.
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceKind {
+	HttpJson {
+		url: String,
+		path: String,
+		/// Sent with every request, e.g. `{"Authorization":"Bearer ..."}`; values are never echoed back
+		#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+		headers: BTreeMap<String, String>,
+	},
+	Helius,
+	Quicknode {
+		#[serde(default)]
+		address: Option<String>,
+	},
+	Static { price: String },
+	Pyth {
+		/// Price account address
+		account: String,
+		/// Solana JSON-RPC endpoint; defaults to `SOLANA_RPC_URL`
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		rpc_url: Option<String>,
+		#[serde(default = "default_max_slot_lag")]
+		max_slot_lag: u64,
+	},
+}
+
+/// One source registered for one mint, e.g. `{"mint":"...","type":"helius","interval_secs":5}`.
+/// A mint with several specs is priced by aggregating their readings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceSpec {
+	pub mint: String,
+	/// Label shown in `/prices/<mint>/sources`; derived from the source type when unset
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(flatten)]
+	pub kind: SourceKind,
+	/// Weight under the `weighted_mean` aggregation method
+	#[serde(default = "default_weight")]
+	pub weight: u32,
+	/// Use `1/x` of the fetched price, e.g. for a USD-per-token feed quoted the other way round
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub invert: bool,
+	/// Decimal factor applied after `invert`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub multiplier: Option<String>,
+	/// Scale prices are rounded to before writing
+	#[serde(default = "default_scale")]
+	pub scale: u32,
+	#[serde(default = "default_interval_secs")]
+	pub interval_secs: u64,
+	/// A fetch taking longer than this counts as failed
+	#[serde(default = "default_timeout_ms")]
+	pub timeout_ms: u64,
+	/// Ceiling for the retry delay after consecutive failures
+	#[serde(default = "default_backoff_max_secs")]
+	pub backoff_max_secs: u64,
+	/// Only write when the price moves more than this from the stored one
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub deviation_bps: Option<u32>,
+	/// Write anyway once the stored price is this old
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub heartbeat_secs: Option<u64>,
+}
+
+fn default_scale() -> u32 { 6 }
+fn default_interval_secs() -> u64 { 15 }
+fn default_weight() -> u32 { 1 }
+fn default_timeout_ms() -> u64 { 5000 }
+fn default_backoff_max_secs() -> u64 { 300 }
+fn default_max_slot_lag() -> u64 { 25 }
+
+/// Shared clients the sources are built from.
+#[derive(Clone)]
+pub struct SourceDeps {
+	pub client: reqwest::Client,
+	pub helius: HeliusPriceService,
+	pub proxy: Arc<QuicknodeProxy>,
+	/// `SOLANA_RPC_URL`, for pyth sources without their own `rpc_url`
+	pub solana_rpc_url: Option<String>,
+}
+
+impl SourceSpec {
+	/// Range checks that hold for every source type.
+	pub fn validate(&self) -> AppResult<()> {
+		if self.scale > MAX_SCALE { return Err(AppError::BadRequest(format!("scale must be <= {}", MAX_SCALE))); }
+		if self.interval_secs == 0 { return Err(AppError::BadRequest("interval_secs must be at least 1".into())); }
+		if self.timeout_ms == 0 { return Err(AppError::BadRequest("timeout_ms must be at least 1".into())); }
+		self.multiplier()?;
+		Ok(())
+	}
+
+	pub fn build(&self, deps: &SourceDeps) -> AppResult<Box<dyn PriceSource>> {
+		self.validate()?;
+		Ok(match &self.kind {
+			SourceKind::HttpJson { url, path, headers } => Box::new(HttpJsonSource {
+				client: deps.client.clone(),
+				url: url.clone(),
+				path: JsonPath::parse(path)?,
+				headers: header_map(headers)?,
+			}),
+			SourceKind::Helius => Box::new(HeliusSource { service: deps.helius.clone() }),
+			SourceKind::Quicknode { address } => Box::new(QuicknodeSource { proxy: deps.proxy.clone(), address: address.clone() }),
+			SourceKind::Static { price } => Box::new(StaticSource { price: UsdAmount::from_decimal_str(price)? }),
+			SourceKind::Pyth { account, rpc_url, max_slot_lag } => Box::new(PythSource {
+				client: deps.client.clone(),
+				rpc_url: rpc_url.clone().or_else(|| deps.solana_rpc_url.clone()).ok_or_else(|| AppError::BadRequest("pyth sources need rpc_url or SOLANA_RPC_URL".into()))?,
+				account: account.clone(),
+				max_slot_lag: *max_slot_lag,
+			}),
+		})
+	}
+
+	/// Delay before the next attempt after `failures` consecutive failures: 1s doubling per failure up
+	/// to `backoff_max_secs`, jittered over the upper half of that so failing sources don't retry in step.
+	pub fn retry_delay(&self, failures: u32) -> Duration {
+		let cap_ms = self.backoff_max_secs.max(1).saturating_mul(1000);
+		let ms = 1000u64.saturating_mul(1 << failures.saturating_sub(1).min(30)).min(cap_ms);
+		Duration::from_millis(rand::thread_rng().gen_range(ms / 2..=ms))
+	}
+
+	/// Chainlink-style write rule. With neither `deviation_bps` nor `heartbeat_secs` set every reading is
+	/// written; otherwise only when `new` moved more than `deviation_bps` (any change if unset) from
+	/// `current`, or `current` is at least `heartbeat_secs` old.
+	pub fn should_write(&self, current: Option<&Price>, new: &UsdAmount, now_ms: i64) -> bool {
+		if self.deviation_bps.is_none() && self.heartbeat_secs.is_none() { return true; }
+		let Some(current) = current else { return true };
+		let moved = match current.usd_amount() {
+			Ok(old) => old.change_bps(new).unwrap_or(if new.is_zero() && old.is_zero() { 0 } else { u128::MAX }),
+			Err(_) => u128::MAX,
+		};
+		if moved > self.deviation_bps.unwrap_or(0) as u128 { return true; }
+		match self.heartbeat_secs {
+			Some(secs) => now_ms.saturating_sub(current.updated_at_ms()) >= (secs as i64).saturating_mul(1000),
+			None => false,
+		}
+	}
+
+	/// Copy with header values masked, for anything leaving the server.
+	pub fn redacted(&self) -> SourceSpec {
+		let mut spec = self.clone();
+		if let SourceKind::HttpJson { headers, .. } = &mut spec.kind {
+			for value in headers.values_mut() { *value = REDACTED.into(); }
+		}
+		spec
+	}
+
+	/// Stored value of header `name` (matched case-insensitively), if this is an `http_json` source.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		match &self.kind {
+			SourceKind::HttpJson { headers, .. } => headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str()),
+			_ => None,
+		}
+	}
+
+	/// `name`, or the source type plus whatever distinguishes it, e.g. `http_json:https://...`.
+	pub fn label(&self) -> String {
+		if let Some(name) = &self.name { return name.clone(); }
+		match &self.kind {
+			SourceKind::HttpJson { url, .. } => format!("http_json:{}", url),
+			SourceKind::Helius => "helius".into(),
+			SourceKind::Quicknode { address: Some(address) } => format!("quicknode:{}", address),
+			SourceKind::Quicknode { address: None } => "quicknode".into(),
+			SourceKind::Static { price } => format!("static:{}", price),
+			SourceKind::Pyth { account, .. } => format!("pyth:{}", account),
+		}
+	}
+
+	fn multiplier(&self) -> AppResult<Option<UsdAmount>> {
+		self.multiplier.as_deref().map(UsdAmount::from_decimal_str).transpose()
+	}
+
+	/// Applies `invert` and `multiplier` to a fetched price and rounds the result to this registration's scale.
+	/// Intermediate results keep every digit the scale allows, so only the final rounding loses precision.
+	pub fn transform(&self, amount: UsdAmount) -> AppResult<UsdAmount> {
+		let overflow = || AppError::BadRequest(format!("transformed price for {} is out of range", self.mint));
+		let mut value = amount;
+		if self.invert {
+			if value.is_zero() { return Err(AppError::NoPrice(format!("cannot invert a zero price for {}", self.mint))); }
+			let one = UsdAmount::new(1, 0)?;
+			value = one.checked_div(&value, (self.scale + 18).min(MAX_SCALE), Rounding::HalfEven).ok_or_else(overflow)?;
+		}
+		if let Some(factor) = self.multiplier()? {
+			value = value.normalize().checked_mul(&factor.normalize()).ok_or_else(overflow)?;
+		}
+		value.rescale(self.scale, Rounding::HalfEven)
+	}
+
+	/// Carries a confidence interval through [`SourceSpec::transform`], keeping its width relative to the
+	/// price: `conf` scales by `transformed / raw`. Rounded up to this registration's scale.
+	pub fn transform_conf(&self, raw: UsdAmount, conf: UsdAmount, transformed: UsdAmount) -> AppResult<UsdAmount> {
+		if (!self.invert && self.multiplier.is_none()) || raw.is_zero() { return conf.rescale(self.scale, Rounding::Ceil); }
+		conf.normalize()
+			.checked_mul(&transformed.normalize())
+			.and_then(|c| c.checked_div(&raw, self.scale, Rounding::Ceil))
+			.ok_or_else(|| AppError::BadRequest(format!("transformed confidence for {} is out of range", self.mint)))
+	}
+}
+
+fn header_map(headers: &BTreeMap<String, String>) -> AppResult<HeaderMap> {
+	let mut map = HeaderMap::new();
+	for (name, value) in headers {
+		let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| AppError::BadRequest(format!("invalid header name {:?}", name)))?;
+		let mut value = HeaderValue::from_str(value).map_err(|_| AppError::BadRequest(format!("invalid value for header {}", name)))?;
+		value.set_sensitive(true);
+		map.insert(name, value);
+	}
+	Ok(map)
+}
+
+/// A source stored in `peg_sources` and managed through `/pegs`. Serializes with header values
+/// redacted, so API responses, audit entries and SSE events never carry credentials.
+#[derive(Debug, Clone)]
+pub struct PegSource {
+	pub id: String,
+	pub spec: SourceSpec,
+	pub created_at: String,
+	pub created_by: String,
+	pub updated_at: String,
+	pub updated_by: String,
+}
+
+impl Serialize for PegSource {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		#[derive(Serialize)]
+		struct Redacted<'a> {
+			id: &'a str,
+			#[serde(flatten)]
+			spec: SourceSpec,
+			created_at: &'a str,
+			created_by: &'a str,
+			updated_at: &'a str,
+			updated_by: &'a str,
+		}
+		Redacted {
+			id: &self.id,
+			spec: self.spec.redacted(),
+			created_at: &self.created_at,
+			created_by: &self.created_by,
+			updated_at: &self.updated_at,
+			updated_by: &self.updated_by,
+		}
+		.serialize(serializer)
+	}
+}
+
+/// Specs from `PRICE_SOURCES` (JSON array of [`SourceSpec`]) followed by the legacy
+/// `PEG_SOURCES` (`mint|url|json.path|scale;...`, polled every 15s). Invalid entries are logged and skipped.
+/// Only used to seed `peg_sources` on first start.
+pub fn specs_from_env() -> Vec<SourceSpec> {
+	let mut specs = Vec::new();
+	if let Ok(raw) = std::env::var("PRICE_SOURCES") {
+		if !raw.trim().is_empty() {
+			match serde_json::from_str::<Vec<SourceSpec>>(&raw) {
+				Ok(parsed) => specs.extend(parsed),
+				Err(e) => tracing::warn!("PRICE_SOURCES is not a valid source list: {}", e),
+			}
+		}
+	}
+	if let Ok(raw) = std::env::var("PEG_SOURCES") {
+		specs.extend(parse_peg_sources(&raw));
+	}
+	specs
+}
+
+/// Parses the legacy `PEG_SOURCES` format into http_json specs.
+pub fn parse_peg_sources(raw: &str) -> Vec<SourceSpec> {
+	raw.split(';')
+		.filter(|s| !s.trim().is_empty())
+		.filter_map(|src| {
+			let parts: Vec<&str> = src.trim().split('|').collect();
+			if parts.len() < 4 {
+				tracing::warn!("skipping PEG_SOURCES entry {:?}: expected mint|url|path|scale", src);
+				return None;
+			}
+			Some(SourceSpec {
+				mint: parts[0].to_string(),
+				name: None,
+				kind: SourceKind::HttpJson { url: parts[1].to_string(), path: parts[2].to_string(), headers: BTreeMap::new() },
+				weight: default_weight(),
+				invert: false,
+				multiplier: None,
+				scale: parts[3].parse().unwrap_or(2),
+				interval_secs: default_interval_secs(),
+				timeout_ms: default_timeout_ms(),
+				backoff_max_secs: default_backoff_max_secs(),
+				deviation_bps: None,
+				heartbeat_secs: None,
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pyth::tests::{account, account_info, mock_rpc};
+
+	/// Static source for `m` with `extra` merged over the defaults.
+	fn spec(extra: serde_json::Value) -> SourceSpec {
+		let mut v = serde_json::json!({ "mint": "m", "type": "static", "price": "1" });
+		for (k, x) in extra.as_object().unwrap() { v[k] = x.clone(); }
+		serde_json::from_value(v).unwrap()
+	}
+
+	fn amount(s: &str) -> UsdAmount {
+		UsdAmount::from_decimal_str(s).unwrap()
+	}
+
+	fn pyth_source(rpc_url: String, max_slot_lag: u64) -> PythSource {
+		PythSource { client: reqwest::Client::new(), rpc_url, account: "acct".into(), max_slot_lag }
+	}
+
+	#[test]
+	fn validate_rejects_out_of_range_specs() {
+		let spec: SourceSpec = serde_json::from_value(serde_json::json!({ "mint": "m", "type": "static", "price": "1.5" })).unwrap();
+		assert!(spec.validate().is_ok());
+		for (field, value) in [("scale", 39u64), ("interval_secs", 0), ("timeout_ms", 0)] {
+			let mut bad = serde_json::to_value(&spec).unwrap();
+			bad[field] = value.into();
+			let bad: SourceSpec = serde_json::from_value(bad).unwrap();
+			assert!(matches!(bad.validate(), Err(AppError::BadRequest(_))), "{} = {}", field, value);
+		}
+	}
+
+	#[tokio::test]
+	async fn pyth_source_rejects_lagging_prices() {
+		let url = mock_rpc(account_info(100, &account(250, 3, -2, 1, 70)));
+		let quote = pyth_source(url.clone(), 30).fetch_quote("mint").await.unwrap();
+		assert_eq!(quote.price.to_string(), "2.50");
+		assert_eq!(quote.conf.unwrap().to_string(), "0.03");
+		let err = pyth_source(url, 29).fetch_quote("mint").await.unwrap_err().to_string();
+		assert!(err.contains("30 slots ago"), "{}", err);
+	}
+
+	#[test]
+	fn transform_inverts_and_multiplies() {
+		let plain = spec(serde_json::json!({}));
+		assert_eq!(plain.transform(amount("1.2345675")).unwrap().to_string(), "1.234568");
+		let inverted = spec(serde_json::json!({ "invert": true }));
+		assert_eq!(inverted.transform(amount("2")).unwrap().to_string(), "0.500000");
+		assert_eq!(inverted.transform(amount("3")).unwrap().to_string(), "0.333333");
+		let scaled = spec(serde_json::json!({ "multiplier": "1.5", "scale": 2 }));
+		assert_eq!(scaled.transform(amount("2")).unwrap().to_string(), "3.00");
+		// inverted first, then multiplied: 1 / 8 * 1000
+		let both = spec(serde_json::json!({ "invert": true, "multiplier": "1000", "scale": 3 }));
+		assert_eq!(both.transform(amount("8")).unwrap().to_string(), "125.000");
+	}
+
+	#[test]
+	fn transform_refuses_to_invert_zero() {
+		let inverted = spec(serde_json::json!({ "invert": true }));
+		assert!(matches!(inverted.transform(UsdAmount::zero()), Err(AppError::NoPrice(_))));
+		assert!(spec(serde_json::json!({})).transform(UsdAmount::zero()).is_ok());
+	}
+
+	#[test]
+	fn transform_conf_keeps_relative_width() {
+		// untransformed: only rounded up to the scale
+		let plain = spec(serde_json::json!({}));
+		assert_eq!(plain.transform_conf(amount("2"), amount("0.0000001"), amount("2")).unwrap().to_string(), "0.000001");
+		// 0.5% of the price either way
+		let scaled = spec(serde_json::json!({ "multiplier": "2" }));
+		assert_eq!(scaled.transform_conf(amount("2"), amount("0.01"), amount("4")).unwrap().to_string(), "0.020000");
+		let inverted = spec(serde_json::json!({ "invert": true }));
+		assert_eq!(inverted.transform_conf(amount("2"), amount("0.01"), amount("0.5")).unwrap().to_string(), "0.002500");
+		// 1/3 of a unit rounds up
+		assert_eq!(inverted.transform_conf(amount("3"), amount("1"), amount("1")).unwrap().to_string(), "0.333334");
+	}
+
+	#[test]
+	fn retry_delay_doubles_up_to_the_cap() {
+		let backoff = spec(serde_json::json!({ "backoff_max_secs": 60 }));
+		// jitter lands in the upper half of the base delay
+		let within = |failures: u32, base_ms: u64| {
+			for _ in 0..50 {
+				let ms = backoff.retry_delay(failures).as_millis() as u64;
+				assert!((base_ms / 2..=base_ms).contains(&ms), "failures {}: {}ms not in {}/2..={}", failures, ms, base_ms, base_ms);
+			}
+		};
+		within(0, 1_000);
+		within(1, 1_000);
+		within(2, 2_000);
+		within(5, 16_000);
+		within(7, 60_000);
+		within(u32::MAX, 60_000);
+	}
+
+	#[test]
+	fn retry_delay_shift_does_not_overflow() {
+		// the shift stops at 2^30 and the multiply saturates even with an unbounded cap
+		let unbounded = spec(serde_json::json!({ "backoff_max_secs": u64::MAX }));
+		let ms = unbounded.retry_delay(u32::MAX).as_millis() as u64;
+		assert!((1000u64 << 29..=1000u64 << 30).contains(&ms), "{}", ms);
+		// a zero cap is treated as one second
+		let zero = spec(serde_json::json!({ "backoff_max_secs": 0 }));
+		assert!(zero.retry_delay(10).as_millis() <= 1_000);
+	}
+
+	#[test]
+	fn should_write_follows_deviation_and_heartbeat() {
+		const T0: i64 = 1_700_000_000_000;
+		let mut current = Price::next_from(None, "m", amount("1.00"), "test");
+		current.updated_at = crate::models::format_ms(T0);
+		let rule = spec(serde_json::json!({ "deviation_bps": 100, "heartbeat_secs": 60 }));
+		// nothing stored yet
+		assert!(rule.should_write(None, &amount("1.00"), T0));
+		// under, at and over the 1% threshold
+		assert!(!rule.should_write(Some(&current), &amount("1.005"), T0 + 1_000));
+		assert!(!rule.should_write(Some(&current), &amount("1.01"), T0 + 1_000));
+		assert!(rule.should_write(Some(&current), &amount("1.0101"), T0 + 1_000));
+		assert!(rule.should_write(Some(&current), &amount("0.98"), T0 + 1_000));
+		// heartbeat expiry writes an unchanged price
+		assert!(!rule.should_write(Some(&current), &amount("1.00"), T0 + 59_999));
+		assert!(rule.should_write(Some(&current), &amount("1.00"), T0 + 60_000));
+		// with neither rule every reading is written; heartbeat alone writes any change
+		assert!(spec(serde_json::json!({})).should_write(Some(&current), &amount("1.00"), T0));
+		let heartbeat_only = spec(serde_json::json!({ "heartbeat_secs": 60 }));
+		assert!(!heartbeat_only.should_write(Some(&current), &amount("1.00"), T0 + 1_000));
+		assert!(heartbeat_only.should_write(Some(&current), &amount("1.000001"), T0 + 1_000));
+	}
+}